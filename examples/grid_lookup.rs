@@ -0,0 +1,78 @@
+//! Compare looking up every position on a map's Buildings layer by scanning
+//! its tiles with looking it up through a `TileGrid`.
+//!
+//! ```text
+//! cargo run --release --example grid_lookup [MAP]...
+//! ```
+//!
+//! MAP is a file in Maps, as for the viewer; it defaults to Town and Farm.
+//! Assets are found the same way as the viewer finds them without
+//! `--assets`.
+
+extern crate sv_map_viewer;
+
+use std::env;
+use std::time::Instant;
+use sv_map_viewer::{AssetSource, grid_for_layer, load_map};
+
+/// The time since `start` in milliseconds.
+fn elapsed_ms(start: Instant) -> f64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() as f64 * 1000. + elapsed.subsec_nanos() as f64 / 1_000_000.
+}
+
+fn main() {
+    let assets = AssetSource::from_config(None);
+    let mut maps: Vec<String> = env::args().skip(1).collect();
+    if maps.is_empty() {
+        maps = vec!["Town.xnb".into(), "Farm.xnb".into()];
+    }
+
+    for map_name in &maps {
+        let map = match load_map(&assets, map_name) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        let layer = match map.layers.iter().find(|l| l.id == "Buildings") {
+            Some(layer) => layer,
+            None => {
+                println!("{}: no Buildings layer", map_name);
+                continue;
+            }
+        };
+        let (w, h) = layer.size;
+
+        let start = Instant::now();
+        let mut found = 0;
+        for y in 0..h {
+            for x in 0..w {
+                if layer.tiles.iter().any(|t| t.get_pos() == (x, y)) {
+                    found += 1;
+                }
+            }
+        }
+        let scan = elapsed_ms(start);
+
+        let start = Instant::now();
+        let grid = grid_for_layer(layer);
+        let build = elapsed_ms(start);
+
+        let start = Instant::now();
+        let mut grid_found = 0;
+        for y in 0..h as i32 {
+            for x in 0..w as i32 {
+                if grid.get(x, y).is_some() {
+                    grid_found += 1;
+                }
+            }
+        }
+        let lookup = elapsed_ms(start);
+
+        println!("{}: {}x{}, {} tiles", map_name, w, h, layer.tiles.len());
+        println!("  scan:  {:.3}ms, {} found", scan, found);
+        println!("  grid:  {:.3}ms (+{:.3}ms to build), {} found", lookup, build, grid_found);
+    }
+}
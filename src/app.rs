@@ -0,0 +1,508 @@
+use SCALE;
+use character::{Character, Player, PlayerDir};
+use graphics::Context;
+use map::{LayerProps, ResolvedTile, SVMap, TileGrid, TileProps};
+use opengl_graphics::{GlGraphics, OpenGL};
+use overlay::Overlays;
+use piston::input::*;
+use render::{Tile, image_for_frame, image_for_texture, image_for_tile};
+use std::collections::VecDeque;
+use std::time::Instant;
+use xnb::tide::Layer;
+
+/// How many frames the frame time statistics are averaged over.
+const FRAME_SAMPLES: usize = 60;
+
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 8.;
+
+fn millis_since(start: Instant) -> f64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() as f64 * 1000. + elapsed.subsec_nanos() as f64 / 1_000_000.
+}
+
+fn push_sample(samples: &mut VecDeque<f64>, sample: f64) {
+    if samples.len() == FRAME_SAMPLES {
+        let _ = samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+pub struct App {
+    gl: GlGraphics,
+    pub view_x: i32,
+    pub view_y: i32,
+    view_w: u32,
+    view_h: u32,
+    ticks: u32,
+    d_pressed: bool,
+    a_pressed: bool,
+    w_pressed: bool,
+    s_pressed: bool,
+    update_last_move: bool,
+    /// Set while an event script is in control of the farmer and camera.
+    pub scripted: bool,
+    /// How many window pixels each map pixel is drawn as.
+    pub zoom: f64,
+    /// Whether the camera keeps the farmer in view. Panning turns this off
+    /// until the farmer moves again.
+    pub follow_player: bool,
+    /// The part of a pan too small to move the view by a whole pixel yet.
+    pan_remainder: (f64, f64),
+    /// The debug overlays drawn over the map.
+    pub overlays: Overlays,
+    /// How long the most recent calls to `render` took, in milliseconds.
+    render_times: VecDeque<f64>,
+    /// The time between the most recent frames, in milliseconds.
+    frame_intervals: VecDeque<f64>,
+    last_frame: Option<Instant>,
+}
+
+impl App {
+    pub fn new(opengl: OpenGL, (view_x, view_y): (i32, i32), (view_w, view_h): (u32, u32)) -> App {
+        App {
+            gl: GlGraphics::new(opengl),
+            view_x: view_x,
+            view_y: view_y,
+            view_w: view_w,
+            view_h: view_h,
+            ticks: 0,
+            a_pressed: false,
+            d_pressed: false,
+            w_pressed: false,
+            s_pressed: false,
+            update_last_move: false,
+            scripted: false,
+            zoom: SCALE,
+            follow_player: true,
+            pan_remainder: (0., 0.),
+            overlays: Overlays::default(),
+            render_times: VecDeque::new(),
+            frame_intervals: VecDeque::new(),
+            last_frame: None,
+        }
+    }
+
+    pub fn render(&mut self,
+                  args: &RenderArgs,
+                  player: &Player,
+                  characters: &[Character],
+                  layers: &[Layer<LayerProps, TileProps>],
+                  resolved_layers: &[TileGrid<ResolvedTile>]) {
+        use graphics::*;
+
+        const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+        let start = Instant::now();
+        if let Some(last) = self.last_frame {
+            push_sample(&mut self.frame_intervals, millis_since(last));
+        }
+        self.last_frame = Some(start);
+
+        let view_x = self.view_x;
+        let view_y = self.view_y;
+        let zoom = self.zoom;
+
+        self.view_w = args.viewport().window_size[0];
+        self.view_h = args.viewport().window_size[1];
+        // The last visible tile column and row at the current zoom.
+        let view_w = (view_x + (self.view_w as f64 / zoom).ceil() as i32) / 16;
+        let view_h = (view_y + (self.view_h as f64 / zoom).ceil() as i32) / 16;
+
+        let ticks = self.ticks;
+        let overlays = self.overlays;
+
+        fn draw_character(character: &Character,
+                          transform: [[f64; 3]; 2],
+                          gl: &mut GlGraphics,
+                          (view_x, view_y): (i32, i32),
+                          (_view_w, _view_h): (i32, i32)) {
+            if character.x < 0 || character.y < 0 {
+                return;
+            }
+            let pos = (character.x, character.y);
+            let offset = (character.offset_x as i32, character.offset_y as i32);
+            let image = match character.frame {
+                Some(frame) => image_for_frame(&character.texture,
+                                               frame,
+                                               pos,
+                                               (view_x, view_y),
+                                               offset,
+                                               character.flip),
+                None => image_for_texture(&character.texture,
+                                          pos,
+                                          (view_x, view_y),
+                                          offset,
+                                          None,
+                                          character.dir),
+            };
+            image.draw(&character.texture.0, &Default::default(), transform, gl);
+        }
+
+        fn draw_layer(layer: &Layer<LayerProps, TileProps>,
+                      resolved_tiles: &TileGrid<ResolvedTile>,
+                      transform: [[f64; 3]; 2],
+                      gl: &mut GlGraphics,
+                      ticks: u32,
+                      (view_x, view_y): (i32, i32),
+                      (view_w, view_h): (i32, i32),
+                      player: Option<&Player>) {
+            if !layer.visible  || layer.id == "Paths" {
+                return;
+            }
+            // The player goes under the tiles from their own column onwards in
+            // the row below them, and everything after that, whichever of
+            // those tiles are in view.
+            let mut player = player;
+            let visible = resolved_tiles.region((view_x / 16, view_y / 16), (view_w, view_h));
+            for ((x, y), resolved) in visible {
+                let tile = Tile {
+                    sheet: resolved.tilesheet,
+                    index: layer.tiles[resolved.tile].get_index(ticks),
+                };
+
+                if let Some(p) = player {
+                    if y > p.y + 1 || (y == p.y + 1 && x >= p.x) {
+                        draw_player(p, gl, transform.clone(), (view_x, view_y), ticks);
+                        player = None;
+                    }
+                }
+
+                let image = image_for_tile(&tile, (x, y), (view_x, view_y));
+                image.draw(resolved.texture, &Default::default(), transform, gl);
+            }
+            if let Some(p) = player {
+                draw_player(p, gl, transform, (view_x, view_y), ticks);
+            }
+        }
+
+        fn draw_player(
+            player: &Player,
+            gl: &mut GlGraphics,
+            transform: [[f64; 3]; 2],
+            view: (i32, i32),
+            ticks: u32,
+        ) {
+            let pos = (player.x as i32, player.y as i32);
+            let offset = (player.offset_x as i32, player.offset_y as i32);
+
+            let player_ticks = match player.last_move_start {
+                Some(start) => ticks - start,
+                None => 0,
+            };
+
+            let three_frame = Some((player_ticks, 3));
+
+            // Body
+            let image = image_for_texture(&player.base, pos, view, offset, three_frame, player.dir);
+            image.draw(&player.base.0, &Default::default(), transform, gl);
+            let image = image_for_texture(&player.bottom, pos, view, offset, three_frame, player.dir);
+            image.draw(&player.bottom.0, &Default::default(), transform, gl);
+
+            // Hair
+            let image = image_for_texture(&player.hairstyle, pos, view, offset, None, player.dir);
+            image.draw(&player.hairstyle.0, &Default::default(), transform, gl);
+
+            // Hat
+            if let Some(ref hat) = player.hat {
+                let image = image_for_texture(hat, pos, view, offset, None, player.dir);
+                image.draw(&hat.0, &Default::default(), transform, gl);
+            }
+
+            // Arms
+            let image = image_for_texture(&player.arms, pos, view, offset, three_frame, player.dir);
+            image.draw(&player.arms.0, &Default::default(), transform, gl);
+
+            // Pants
+            let image = image_for_texture(&player.pants, pos, view, offset, three_frame, player.dir);
+            image.draw(&player.pants.0, &Default::default(), transform, gl);
+
+            // Shirt
+            let image = image_for_texture(&player.shirt, pos, view, offset, None, player.dir);
+            image.draw(&player.shirt.0, &Default::default(), transform, gl);
+
+            // Facial accessory
+            if player.dir != PlayerDir::Up {
+                let image = image_for_texture(&player.accessory, pos, view, offset, None, player.dir);
+                image.draw(&player.accessory.0, &Default::default(), transform, gl);
+            }
+        }
+
+        fn draw_overlays(overlays: Overlays,
+                         layers: &[Layer<LayerProps, TileProps>],
+                         resolved_layers: &[TileGrid<ResolvedTile>],
+                         transform: [[f64; 3]; 2],
+                         gl: &mut GlGraphics,
+                         ticks: u32,
+                         (view_x, view_y): (i32, i32),
+                         (view_w, view_h): (i32, i32)) {
+            let find = |id: &str| layers.iter().position(|l| l.id == id);
+            let (back, buildings) = (find("Back"), find("Buildings"));
+            let tile_at = |layer: Option<usize>, x: i32, y: i32| {
+                let i = layer?;
+                let resolved = resolved_layers[i].get(x, y)?;
+                Some((&resolved.tilesheet.properties, layers[i].tiles[resolved.tile].get_index(ticks)))
+            };
+            // Only tint tiles on the map, like `TileGrid::region`.
+            let size = |layer: Option<usize>| layer.map_or((0, 0), |i| layers[i].size);
+            let (back_size, buildings_size) = (size(back), size(buildings));
+            let map_w = back_size.0.max(buildings_size.0) as i32;
+            let map_h = back_size.1.max(buildings_size.1) as i32;
+            for y in (view_y / 16).max(0)..(view_h + 1).min(map_h) {
+                for x in (view_x / 16).max(0)..(view_w + 1).min(map_w) {
+                    let tints = overlays.tints(tile_at(back, x, y), tile_at(buildings, x, y));
+                    let rect = [(x * 16 - view_x) as f64, (y * 16 - view_y) as f64, 16., 16.];
+                    for tint in tints {
+                        rectangle(tint, rect, transform, gl);
+                    }
+                }
+            }
+        }
+
+        self.gl.draw(args.viewport(), |c, gl| {
+            // Clear the screen.
+            clear(BLACK, gl);
+
+            let transform = c.transform.zoom(zoom);
+
+            for (i, (layer, resolved)) in layers.iter().zip(resolved_layers).enumerate() {
+                if i == layers.len() - 1 {
+                    break;
+                }
+                let player = if i == 1 { Some(player) } else { None };
+                draw_layer(layer, resolved, transform, gl, ticks,
+                           (view_x, view_y), (view_w, view_h), player);
+            }
+
+            for character in characters {
+                draw_character(character, transform, gl,
+                               (view_x, view_y), (view_w, view_h));
+            }
+
+            draw_layer(layers.last().unwrap(),
+                       resolved_layers.last().unwrap(),
+                       transform, gl, ticks,
+                       (view_x, view_y), (view_w, view_h), None);
+
+            if overlays.any() {
+                draw_overlays(overlays, layers, resolved_layers, transform, gl, ticks,
+                              (view_x, view_y), (view_w, view_h));
+            }
+        });
+
+        push_sample(&mut self.render_times, millis_since(start));
+    }
+
+    /// Milliseconds of game time since the app started.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// The map tile under the window position `(x, y)`.
+    pub fn tile_at(&self, (x, y): (f64, f64)) -> (i32, i32) {
+        let map_x = x / self.zoom + self.view_x as f64;
+        let map_y = y / self.zoom + self.view_y as f64;
+        ((map_x / 16.).floor() as i32, (map_y / 16.).floor() as i32)
+    }
+
+    /// The rectangle of the window that the map tile at `(x, y)` is drawn in.
+    pub fn tile_rect(&self, (x, y): (i32, i32)) -> [f64; 4] {
+        [(x * 16 - self.view_x) as f64 * self.zoom,
+         (y * 16 - self.view_y) as f64 * self.zoom,
+         16. * self.zoom,
+         16. * self.zoom]
+    }
+
+    /// The centre of the window, in window pixels.
+    pub fn window_center(&self) -> (f64, f64) {
+        (self.view_w as f64 / 2., self.view_h as f64 / 2.)
+    }
+
+    /// Multiply the zoom by `factor`, keeping the map pixel under the window
+    /// position `(x, y)` in place.
+    pub fn zoom_by(&mut self, factor: f64, (x, y): (f64, f64)) {
+        let zoom = (self.zoom * factor).max(MIN_ZOOM).min(MAX_ZOOM);
+        let map_x = x / self.zoom + self.view_x as f64;
+        let map_y = y / self.zoom + self.view_y as f64;
+        // Like `pan`, never scroll past the map's top left, where dividing a
+        // negative origin into tiles would round toward the wrong tile.
+        self.view_x = ((map_x - x / zoom).round() as i32).max(0);
+        self.view_y = ((map_y - y / zoom).round() as i32).max(0);
+        self.zoom = zoom;
+    }
+
+    /// Zoom so that a map of `size` tiles fits in the window, and show all
+    /// of it.
+    pub fn fit_to_window(&mut self, size: (u32, u32)) {
+        let zoom_w = self.view_w as f64 / (size.0 * 16).max(1) as f64;
+        let zoom_h = self.view_h as f64 / (size.1 * 16).max(1) as f64;
+        self.zoom = zoom_w.min(zoom_h).max(MIN_ZOOM).min(MAX_ZOOM);
+        self.view_x = 0;
+        self.view_y = 0;
+        self.follow_player = false;
+    }
+
+    /// Centre the view on the tile at `(x, y)` of a map of `size` tiles, and
+    /// follow the farmer from there.
+    pub fn center_on(&mut self, (x, y): (i32, i32), size: (u32, u32)) {
+        let (view_w, view_h) = ((self.view_w as f64 / self.zoom) as i32, (self.view_h as f64 / self.zoom) as i32);
+        let max_x = size.0 as i32 * 16 - view_w;
+        let max_y = size.1 as i32 * 16 - view_h;
+        self.view_x = (x * 16 + 8 - view_w / 2).min(max_x).max(0);
+        self.view_y = (y * 16 + 8 - view_h / 2).min(max_y).max(0);
+        self.pan_remainder = (0., 0.);
+        self.follow_player = true;
+    }
+
+    /// Move the view by `(dx, dy)` map pixels, and stop following the
+    /// farmer.
+    pub fn pan(&mut self, (dx, dy): (f64, f64)) {
+        let x = self.pan_remainder.0 + dx;
+        let y = self.pan_remainder.1 + dy;
+        self.pan_remainder = (x.fract(), y.fract());
+        self.view_x = (self.view_x + x.trunc() as i32).max(0);
+        self.view_y = (self.view_y + y.trunc() as i32).max(0);
+        self.follow_player = false;
+    }
+
+    /// The average time spent in `render` and the average time between
+    /// frames over the last few frames, in milliseconds.
+    pub fn frame_times(&self) -> (f64, f64) {
+        fn average(samples: &VecDeque<f64>) -> f64 {
+            if samples.is_empty() {
+                return 0.;
+            }
+            samples.iter().sum::<f64>() / samples.len() as f64
+        }
+        (average(&self.render_times), average(&self.frame_intervals))
+    }
+
+    /// Draw on top of the most recently rendered frame, in window coordinates.
+    pub fn draw_overlay<F: FnOnce(Context, &mut GlGraphics)>(&mut self, args: &RenderArgs, f: F) {
+        self.gl.draw(args.viewport(), f);
+    }
+
+    pub fn key_released(&mut self, key: Key) {
+        match key {
+            Key::A => self.a_pressed = false,
+            Key::D => self.d_pressed = false,
+            Key::W => self.w_pressed = false,
+            Key::S => self.s_pressed = false,
+            _ => {}
+        }
+
+        self.update_last_move = true;
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        if key == Key::W && !self.w_pressed ||
+            key == Key::S && !self.s_pressed ||
+            key == Key::A && !self.a_pressed ||
+            key == Key::D && !self.d_pressed {
+            self.update_last_move = true
+        }
+
+        match key {
+            Key::A => self.a_pressed = true,
+            Key::D => self.d_pressed = true,
+            Key::S => self.s_pressed = true,
+            Key::W => self.w_pressed = true,
+            _ => return,
+        }
+        self.follow_player = true;
+    }
+
+    pub fn update(&mut self,
+                  args: &UpdateArgs,
+                  player: &mut Player,
+                  map: &SVMap,
+                  resolved_layers: &[TileGrid<ResolvedTile>]) {
+        self.ticks += (args.dt * 1000.) as u32;
+
+        if self.scripted {
+            return;
+        }
+
+        if self.update_last_move {
+            self.update_last_move = false;
+            if self.a_pressed || self.d_pressed || self.s_pressed || self.w_pressed {
+                if self.w_pressed {
+                    player.dir = PlayerDir::Up;
+                }
+                if self.s_pressed {
+                    player.dir = PlayerDir::Down;
+                }
+                if self.a_pressed {
+                    player.dir = PlayerDir::Left;
+                }
+                if self.d_pressed {
+                    player.dir = PlayerDir::Right;
+                }
+                player.last_move_start = Some(self.ticks);
+            } else {
+                player.last_move_start = None;
+            }
+        }
+
+        const MOVE_AMOUNT: f64 = 100.0;
+        let delta_x = if self.a_pressed {
+            -MOVE_AMOUNT * args.dt
+        } else if self.d_pressed {
+            MOVE_AMOUNT * args.dt
+        } else {
+            0.
+        };
+
+        let delta_y = if self.w_pressed {
+            -MOVE_AMOUNT * args.dt
+        } else if self.s_pressed {
+            MOVE_AMOUNT * args.dt
+        } else {
+            0.
+        };
+
+        let (adjusted_x, adjusted_y) = player.adjusted_pos(delta_x, delta_y);
+        // A map without a Buildings layer has nothing to collide with.
+        let buildings = map.layers.iter().position(|l| l.id == "Buildings");
+        let blocked = buildings.and_then(|b| {
+            let resolved = resolved_layers[b].get(adjusted_x, adjusted_y + 1)?;
+            let index = map.layers[b].tiles[resolved.tile].get_index(0);
+            Some(!resolved.tilesheet.properties.tile_is_passable(index).unwrap_or(false))
+        });
+        let clamp_to_current_pos = blocked.unwrap_or(false);
+
+        player.move_horiz(delta_x, clamp_to_current_pos);
+        player.move_vert(delta_y, clamp_to_current_pos);
+
+        let player_x = player.x * 16 + player.offset_x as i32;
+        let player_y = player.y * 16 + player.offset_y as i32;
+
+        let (view_w, view_h) = ((self.view_w as f64 / self.zoom) as i32, (self.view_h as f64 / self.zoom) as i32);
+
+        let adjusted_x = if !self.follow_player {
+            self.view_x
+        } else if player_x - self.view_x < view_w / 3 {
+            player_x - view_w / 3
+        } else if player_x - self.view_x > view_w / 3 * 2 {
+            player_x - view_w / 3 * 2
+        } else {
+            self.view_x
+        };
+
+        let adjusted_y = if !self.follow_player {
+            self.view_y
+        } else if player_y - self.view_y < view_h / 3 {
+            player_y - view_h / 3
+        } else if player_y - self.view_y > view_h / 3 * 2 {
+            player_y - view_h / 3 * 2
+        } else {
+            self.view_y
+        };
+
+        // A map smaller than the window when zoomed out stays at its top left.
+        let max_x = map.layers[0].size.0 as i32 * 16 - view_w;
+        let max_y = map.layers[0].size.1 as i32 * 16 - view_h;
+        self.view_x = adjusted_x.min(max_x).max(0);
+        self.view_y = adjusted_y.min(max_y).max(0);
+    }
+}
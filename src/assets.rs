@@ -0,0 +1,149 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// The environment variable holding a list of asset roots.
+pub const ASSET_ROOT_VAR: &'static str = "SV_ASSET_ROOT";
+/// The config file consulted when neither a flag nor the environment
+/// variable names an asset root.
+pub const CONFIG_FILE: &'static str = "sv-map-viewer.conf";
+const DEFAULT_ROOT: &'static str = "../xnb/uncompressed";
+
+/// A game asset that couldn't be found in any asset root.
+#[derive(Debug)]
+pub struct AssetError {
+    pub path: PathBuf,
+    pub searched: Vec<PathBuf>,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "couldn't find {}; searched:", self.path.display())?;
+        for path in &self.searched {
+            write!(f, "\n  {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AssetError {
+    fn description(&self) -> &str {
+        "asset not found"
+    }
+}
+
+impl From<AssetError> for io::Error {
+    fn from(e: AssetError) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, e.to_string())
+    }
+}
+
+/// The set of directories that game content is loaded from. Later roots
+/// are only consulted for files missing from earlier ones, so a directory of
+/// modified files can be layered over the unpacked game content.
+pub struct AssetSource {
+    roots: Vec<PathBuf>,
+    /// Load unpacked `.png` images in preference to the `.xnb` textures
+    /// they sit next to, rather than only when the `.xnb` is missing.
+    pub prefer_png: bool,
+}
+
+/// Read `asset_root = <path>` lines from a config file.
+fn roots_from_config(path: &Path) -> Vec<PathBuf> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return vec![],
+    };
+    BufReader::new(f).lines().filter_map(|line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return None,
+        };
+        let mut parts = line.splitn(2, '=');
+        match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+            (Some("asset_root"), Some(root)) => Some(PathBuf::from(root)),
+            _ => None,
+        }
+    }).collect()
+}
+
+impl AssetSource {
+    pub fn new(roots: Vec<PathBuf>) -> AssetSource {
+        AssetSource {
+            roots: roots,
+            prefer_png: false,
+        }
+    }
+
+    /// Find the asset roots to use: the command-line `flag` if given, then the
+    /// `SV_ASSET_ROOT` environment variable, then `asset_root` entries in
+    /// `sv-map-viewer.conf`, and finally `../xnb/uncompressed`.
+    pub fn from_config(flag: Option<&str>) -> AssetSource {
+        if let Some(root) = flag {
+            return AssetSource::new(vec![PathBuf::from(root)]);
+        }
+        if let Some(roots) = env::var_os(ASSET_ROOT_VAR) {
+            let roots: Vec<_> = env::split_paths(&roots).collect();
+            if !roots.is_empty() {
+                return AssetSource::new(roots);
+            }
+        }
+        let roots = roots_from_config(Path::new(CONFIG_FILE));
+        if !roots.is_empty() {
+            return AssetSource::new(roots);
+        }
+        AssetSource::new(vec![PathBuf::from(DEFAULT_ROOT)])
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// The full path of the asset at `path`, relative to the asset roots.
+    pub fn find<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, AssetError> {
+        let path = path.as_ref();
+        let candidates: Vec<_> = self.roots.iter().map(|r| r.join(path)).collect();
+        match candidates.iter().find(|c| c.exists()) {
+            Some(found) => Ok(found.clone()),
+            None => Err(AssetError {
+                path: path.to_owned(),
+                searched: candidates,
+            }),
+        }
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.find(path).is_ok()
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<File, AssetError> {
+        let found = self.find(path.as_ref())?;
+        File::open(&found).map_err(|_| AssetError {
+            path: path.as_ref().to_owned(),
+            searched: vec![found],
+        })
+    }
+
+    /// The names of the files in the directory `dir` across all roots.
+    pub fn list<P: AsRef<Path>>(&self, dir: P) -> Vec<String> {
+        let mut names = vec![];
+        for root in &self.roots {
+            let entries = match fs::read_dir(root.join(dir.as_ref())) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.filter_map(|e| e.ok()) {
+                if let Some(name) = entry.file_name().to_str() {
+                    if !names.iter().any(|n| n == name) {
+                        names.push(name.to_owned());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,127 @@
+use event::{ParseMode, event_keys, parse_event};
+use font::Font;
+use graphics::{Context, Transformed, rectangle};
+use opengl_graphics::GlGraphics;
+use piston::input::Key;
+use std::collections::HashMap;
+
+const PANEL_WIDTH: f64 = 360.;
+const ROW_PADDING: f64 = 4.;
+const TEXT_SCALE: f64 = 1.;
+
+/// A summary of one entry in an events dictionary.
+pub struct EventEntry {
+    pub key: String,
+    pub id: String,
+    pub music: String,
+    pub participants: Vec<String>,
+    pub commands: usize,
+    pub error: Option<String>,
+}
+
+/// An overlay listing the events for the current map, from which one can be
+/// picked to play.
+pub struct EventBrowser {
+    pub open: bool,
+    entries: Vec<EventEntry>,
+    selected: usize,
+    scroll: usize,
+}
+
+impl EventBrowser {
+    /// List the events in `sources`, leaving out fork scripts, which can't
+    /// be played on their own.
+    pub fn new(sources: &HashMap<String, String>) -> EventBrowser {
+        let mut entries: Vec<_> = event_keys(sources).into_iter().map(|key| {
+            let id = key.split('/').next().unwrap_or("").to_owned();
+            match parse_event(&key, sources, ParseMode::Lenient) {
+                Ok(event) => EventEntry {
+                    key: key,
+                    id: id,
+                    music: event.music.clone(),
+                    participants: event.characters.iter().map(|c| c.name.clone()).collect(),
+                    commands: event.commands.len(),
+                    error: None,
+                },
+                Err(e) => EventEntry {
+                    key: key,
+                    id: id,
+                    music: String::new(),
+                    participants: vec![],
+                    commands: 0,
+                    error: Some(e.to_string()),
+                },
+            }
+        }).collect();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+        EventBrowser {
+            open: false,
+            entries: entries,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[EventEntry] {
+        &self.entries
+    }
+
+    /// Handle a key press while the browser is open. Returns the key of the
+    /// event to play if one was chosen.
+    pub fn key_pressed(&mut self, key: Key) -> Option<String> {
+        match key {
+            Key::Up if self.selected > 0 => self.selected -= 1,
+            Key::Down if self.selected + 1 < self.entries.len() => self.selected += 1,
+            Key::Return => {
+                let entry = self.entries.get(self.selected);
+                if let Some(entry) = entry {
+                    if entry.error.is_none() {
+                        self.open = false;
+                        return Some(entry.key.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(&mut self, font: &Font, c: Context, gl: &mut GlGraphics) {
+        const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+        const HIGHLIGHT: [f32; 4] = [0.3, 0.3, 0.6, 0.9];
+        const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+        const ERROR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+        let height = c.get_view_size()[1];
+        let row_height = font.line_height() * 2. * TEXT_SCALE + ROW_PADDING;
+        let rows = ((height / row_height) as usize).max(1);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+
+        rectangle(BACKGROUND, [0., 0., PANEL_WIDTH, height], c.transform, gl);
+
+        let visible = self.entries.iter().enumerate().skip(self.scroll).take(rows);
+        for (row, (i, entry)) in visible.enumerate() {
+            let y = row as f64 * row_height;
+            if i == self.selected {
+                rectangle(HIGHLIGHT, [0., y, PANEL_WIDTH, row_height], c.transform, gl);
+            }
+            let transform = c.transform.trans(ROW_PADDING, y + ROW_PADDING / 2.);
+            let (summary, color) = match entry.error {
+                Some(ref e) => (format!("{}\n{}", entry.id, e), ERROR),
+                None => (format!("{} ({}, {} commands)\n{}",
+                                 entry.id,
+                                 entry.music,
+                                 entry.commands,
+                                 entry.participants.join(", ")),
+                         TEXT),
+            };
+            font.draw(&summary, (0., 0.), TEXT_SCALE, color, transform, gl);
+        }
+    }
+}
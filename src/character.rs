@@ -0,0 +1,241 @@
+use assets::AssetSource;
+use event::ScriptedEvent;
+use texture::{TextureError, TextureTileInfo, load_texture};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PlayerDir {
+    Down = 0,
+    Right = 1,
+    Up = 2,
+    Left = 3,
+}
+
+impl PlayerDir {
+    /// Convert a facing direction as written in event scripts.
+    pub fn from_event_dir(dir: u8) -> Option<PlayerDir> {
+        match dir {
+            0 => Some(PlayerDir::Up),
+            1 => Some(PlayerDir::Right),
+            2 => Some(PlayerDir::Down),
+            3 => Some(PlayerDir::Left),
+            _ => None,
+        }
+    }
+}
+
+pub struct Character {
+    pub name: String,
+    pub texture: TextureTileInfo,
+    /// A specific sprite frame to draw instead of the directional one.
+    pub frame: Option<u32>,
+    pub flip: bool,
+    pub x: i32,
+    pub y: i32,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub dir: PlayerDir,
+}
+
+pub struct Player {
+    pub base: TextureTileInfo,
+    pub bottom: TextureTileInfo,
+    pub arms: TextureTileInfo,
+    pub pants: TextureTileInfo,
+    pub hairstyle: TextureTileInfo,
+    pub hat: Option<TextureTileInfo>,
+    pub shirt: TextureTileInfo,
+    pub accessory: TextureTileInfo,
+    pub x: i32,
+    pub y: i32,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub last_move_start: Option<u32>,
+    pub dir: PlayerDir,
+}
+
+impl Player {
+    /// Load the farmer's textures from the `Characters` directory.
+    pub fn load(assets: &AssetSource) -> Result<Player, TextureError> {
+        let base = load_texture(assets, "Characters/Farmer/farmer_base.xnb")?;
+        let bottom = load_texture(assets, "Characters/Farmer/farmer_base.xnb")?;
+        let arms = load_texture(assets, "Characters/Farmer/farmer_base.xnb")?;
+        let pants = load_texture(assets, "Characters/Farmer/farmer_base.xnb")?;
+        let hairstyle = load_texture(assets, "Characters/Farmer/hairstyles.xnb")?;
+        //let hat = load_texture(assets, "Characters/Farmer/hats.xnb")?;
+        let shirt = load_texture(assets, "Characters/Farmer/shirts.xnb")?;
+        let accessory = load_texture(assets, "Characters/Farmer/accessories.xnb")?;
+        let base_dir_info = [Some(0), Some(2), Some(4), Some(2)];
+        Ok(Player {
+            base: (base, 0, (16, 16), (0, 0), base_dir_info),
+            bottom: (bottom, 24, (16, 16), (0, 16), base_dir_info),
+            arms: (arms, 30, (16, 16), (0, 16), base_dir_info),
+            hairstyle: (hairstyle, 0, (16, 16), (0, 0), base_dir_info),
+            //hat: (hat, 2, (20, 20), (-2, -2), [Some(0), Some(1), Some(3), Some(2)]),
+            hat: None,
+            pants: (pants, 42, (16, 16), (0, 16), base_dir_info),
+            shirt: (shirt, 0, (8, 8), (4, 15), [Some(0), Some(1), Some(3), Some(2)]),
+            accessory: (accessory, 0, (16, 16), (0, 3), [Some(0), Some(1), None, Some(1)]),
+            x: 10,
+            y: 15,
+            offset_x: 0.,
+            offset_y: 0.,
+            last_move_start: None,
+            dir: PlayerDir::Down,
+        })
+    }
+
+    pub fn adjusted_pos(&self, delta_x: f64, delta_y: f64) -> (i32, i32) {
+        let x = self.x + (if delta_x < 0. && self.offset_x + delta_x < -8. {
+            -1
+        } else if delta_x > 0. && self.offset_x + delta_x > 8. {
+           1
+        } else {
+            0
+        });
+
+        let y = self.y + (if delta_y < 0. && self.offset_y + delta_y < -8. {
+            -1
+        } else if delta_y > 0. && self.offset_y + delta_y > 8. {
+           1
+        } else {
+            0
+        });
+
+        (x, y)
+    }
+
+    pub fn move_horiz(&mut self, delta: f64, clamp_to_current_pos: bool) {
+        self.offset_x += delta;
+        if delta < 0. && self.offset_x < -8. {
+            if clamp_to_current_pos {
+                self.offset_x = -7.99;
+            } else {
+                self.offset_x = 8.;
+                self.x -= 1;
+            }
+        } else if delta > 0. && self.offset_x > 8. {
+            if clamp_to_current_pos {
+                self.offset_x = 7.99;
+            } else {
+                self.offset_x = -8.;
+                self.x += 1;
+            }
+        }
+    }
+
+    pub fn move_vert(&mut self, delta: f64, clamp_to_current_pos: bool) {
+        self.offset_y += delta;
+        if delta < 0. && self.offset_y < -8. {
+            if clamp_to_current_pos {
+                self.offset_y = -7.99;
+            } else {
+                self.offset_y = 8.;
+                self.y -= 1;
+            }
+        } else if delta > 0. && self.offset_y > 8. {
+            if clamp_to_current_pos {
+                self.offset_y = 7.99;
+            } else {
+                self.offset_y = -8.;
+                self.y += 1;
+            }
+        }
+    }
+}
+
+/// Something that can be positioned and turned by an event script.
+pub trait Actor {
+    fn position(&self) -> (i32, i32);
+    fn offset(&self) -> (f64, f64);
+    fn set_position(&mut self, pos: (i32, i32), offset: (f64, f64));
+    fn set_dir(&mut self, dir: PlayerDir);
+
+    /// The absolute position of this actor in map pixels.
+    fn pixel_position(&self) -> (f64, f64) {
+        let (x, y) = self.position();
+        let (offset_x, offset_y) = self.offset();
+        ((x * 16) as f64 + offset_x, (y * 16) as f64 + offset_y)
+    }
+
+    fn set_pixel_position(&mut self, pos: (f64, f64)) {
+        let (px, py) = pos;
+        let x = (px / 16.).round() as i32;
+        let y = (py / 16.).round() as i32;
+        self.set_position((x, y), (px - (x * 16) as f64, py - (y * 16) as f64));
+    }
+}
+
+impl Actor for Player {
+    fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn offset(&self) -> (f64, f64) {
+        (self.offset_x, self.offset_y)
+    }
+
+    fn set_position(&mut self, (x, y): (i32, i32), (offset_x, offset_y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+    }
+
+    fn set_dir(&mut self, dir: PlayerDir) {
+        self.dir = dir;
+    }
+}
+
+impl Actor for Character {
+    fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn offset(&self) -> (f64, f64) {
+        (self.offset_x, self.offset_y)
+    }
+
+    fn set_position(&mut self, (x, y): (i32, i32), (offset_x, offset_y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+    }
+
+    fn set_dir(&mut self, dir: PlayerDir) {
+        self.dir = dir;
+    }
+}
+
+/// Load the NPCs that `event` starts with. Characters whose textures can't be
+/// loaded are left out, and their errors returned alongside the rest.
+pub fn characters_for_event(event: &ScriptedEvent,
+                            assets: &AssetSource) -> (Vec<Character>, Vec<TextureError>) {
+    let mut characters = vec![];
+    let mut errors = vec![];
+    for character in &event.characters {
+        if character.name == "farmer" {
+            continue;
+        }
+        let texture = match load_texture(assets, &format!("Characters/{}.xnb", character.name)) {
+            Ok(texture) => texture,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let info = (texture, 0, (16, 32), (0, 0), [Some(0), Some(1), Some(2), Some(3)]);
+        characters.push(Character {
+            texture: info,
+            name: character.name.clone(),
+            x: character.pos.0,
+            y: character.pos.1,
+            offset_x: 0.,
+            offset_y: 0.,
+            frame: None,
+            flip: false,
+            dir: PlayerDir::from_event_dir(character.dir).unwrap_or(PlayerDir::Down),
+        });
+    }
+    (characters, errors)
+}
@@ -0,0 +1,388 @@
+use std::iter::Peekable;
+use std::str::FromStr;
+use sv_map_viewer::Overlays;
+use std::vec;
+
+pub const USAGE: &'static str = "\
+usage: spinning-square [--assets DIR] [--prefer-png] <command> [options]
+       spinning-square [--assets DIR] [--prefer-png] [MAP]
+
+commands:
+  view [MAP] [--event ID] [--at X,Y]
+      Open MAP in a window, optionally playing event ID or starting with
+      the view at tile X,Y. F5 to F12 toggle the passable, water, types,
+      spawnable, diggable, shadow, paths and other overlays. T toggles
+      the tile inspector, which describes the tile under the mouse; click
+      a tile to keep it selected. The mouse wheel and +/- zoom, 0 fits the
+      map to the window, and dragging or the arrow keys pan the view
+      until the farmer moves again.
+  render [MAP] [--output FILE] [--rect X,Y,W,H] [--overlay LIST]
+      Draw MAP (or the tile rectangle X,Y,W,H of it) to a PNG. LIST is a
+      comma-separated list of debug overlays to tint the tiles with:
+      passable, water, types, spawnable, diggable, shadow, paths (tiles
+      with a PathType) and other (tiles with unrecognised properties).
+  dump [MAP] [--format text|json|tmx] [--output DIR] [--event ID]
+      Print MAP's layers and tilesheets, as text or JSON. With --format tmx,
+      write it to DIR (by default the current directory) as a Tiled map
+      along with its tilesheets as PNGs. With --event, print the parsed
+      event ID from MAP's events as JSON instead.
+  events [MAP] [--set KEY=VALUE]...
+      List the events on MAP whose preconditions hold for the given state.
+  validate
+      Check every event file for parse failures and bad references.
+  tiles [MAP] [--layer NAME]
+      List the position, tilesheet and index of every tile on MAP.
+
+options:
+  --assets DIR   Load game content from DIR. Defaults to $SV_ASSET_ROOT,
+                 then asset_root in sv-map-viewer.conf, then
+                 ../xnb/uncompressed.
+  --prefer-png   Load NAME.png instead of NAME.xnb for textures that have
+                 both. Without this, the .png is only used when the .xnb is
+                 missing.
+  -h, --help     Show this message.
+
+Options must come before the command. A MAP with no command is viewed.
+MAP is a file in Maps, with or without .xnb; it defaults to Town. Maps
+saved by Tiled (.tmx) or tIDE (.tbin) can be used too.
+";
+
+const DEFAULT_MAP: &'static str = "Town.xnb";
+
+pub enum Command {
+    View {
+        map: String,
+        event: Option<String>,
+        at: Option<(i32, i32)>,
+    },
+    Render {
+        map: String,
+        output: String,
+        rect: Option<(u32, u32, u32, u32)>,
+        overlays: Overlays,
+    },
+    Dump {
+        map: String,
+        format: DumpFormat,
+        output: String,
+        event: Option<String>,
+    },
+    Events {
+        map: String,
+        settings: Vec<String>,
+    },
+    Validate,
+    Tiles {
+        map: String,
+        layer: Option<String>,
+    },
+    Help,
+}
+
+pub enum DumpFormat {
+    Text,
+    Json,
+    Tmx,
+}
+
+pub struct Options {
+    pub assets: Option<String>,
+    pub prefer_png: bool,
+    pub command: Command,
+}
+
+/// Add the `.xnb` extension to a map name if it doesn't name a map file.
+fn map_file(name: String) -> String {
+    if name.ends_with(".xnb") || name.ends_with(".tmx") || name.ends_with(".tbin") {
+        name
+    } else {
+        format!("{}.xnb", name)
+    }
+}
+
+/// Parse a comma-separated list of exactly `count` numbers given to `flag`.
+fn parse_list<T: FromStr>(flag: &str, value: &str, count: usize) -> Result<Vec<T>, String> {
+    let values: Vec<_> = value.split(',').map(|v| v.trim().parse()).collect();
+    if values.len() != count || values.iter().any(|v| v.is_err()) {
+        return Err(format!("{} expects {} comma-separated numbers, got {:?}", flag, count, value));
+    }
+    Ok(values.into_iter().filter_map(|v| v.ok()).collect())
+}
+
+/// Parse the comma-separated overlay names given to `--overlay`.
+fn parse_overlays(value: &str) -> Result<Overlays, String> {
+    let mut overlays = Overlays::default();
+    for name in value.split(',').map(|n| n.trim()) {
+        match name {
+            "passable" => overlays.passable = true,
+            "water" => overlays.water = true,
+            "types" => overlays.types = true,
+            "spawnable" => overlays.spawnable = true,
+            "diggable" => overlays.diggable = true,
+            "shadow" => overlays.shadow = true,
+            "paths" => overlays.path_types = true,
+            "other" => overlays.other = true,
+            other => return Err(format!("unknown overlay {:?}", other)),
+        }
+    }
+    Ok(overlays)
+}
+
+/// Whether `arg` asks for the usage message.
+fn is_help(arg: &str) -> bool {
+    arg == "-h" || arg == "--help"
+}
+
+struct Args {
+    args: Peekable<vec::IntoIter<String>>,
+    command: &'static str,
+}
+
+impl Args {
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+
+    fn peek(&mut self) -> Option<&str> {
+        self.args.peek().map(|s| &s[..])
+    }
+
+    /// The value following `flag`, which can't be another option.
+    fn value(&mut self, flag: &str) -> Result<String, String> {
+        match self.next() {
+            Some(ref value) if value.starts_with("--") => Err(format!("{} expects a value, got {:?}", flag, value)),
+            Some(value) => Ok(value),
+            None => Err(format!("{} expects a value", flag)),
+        }
+    }
+
+    fn unexpected(&self, arg: &str) -> String {
+        format!("unexpected argument {:?} for {}", arg, self.command)
+    }
+}
+
+fn parse_view(args: &mut Args) -> Result<Command, String> {
+    let mut map = None;
+    let mut event = None;
+    let mut at = None;
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--event" => event = Some(args.value("--event")?),
+            "--at" => {
+                let v = parse_list("--at", &args.value("--at")?, 2)?;
+                at = Some((v[0], v[1]));
+            }
+            _ if is_help(&arg) => return Ok(Command::Help),
+            _ if map.is_none() && !arg.starts_with('-') => map = Some(arg.clone()),
+            _ => return Err(args.unexpected(&arg)),
+        }
+    }
+    Ok(Command::View {
+        map: map_file(map.unwrap_or(DEFAULT_MAP.into())),
+        event: event,
+        at: at,
+    })
+}
+
+fn parse_render(args: &mut Args) -> Result<Command, String> {
+    let mut map = None;
+    let mut output = None;
+    let mut rect = None;
+    let mut overlays = Overlays::default();
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "-o" | "--output" => output = Some(args.value("--output")?),
+            "--rect" => {
+                let v = parse_list("--rect", &args.value("--rect")?, 4)?;
+                rect = Some((v[0], v[1], v[2], v[3]));
+            }
+            "--overlay" => overlays = parse_overlays(&args.value("--overlay")?)?,
+            _ if is_help(&arg) => return Ok(Command::Help),
+            _ if map.is_none() && !arg.starts_with('-') => map = Some(arg.clone()),
+            _ => return Err(args.unexpected(&arg)),
+        }
+    }
+    Ok(Command::Render {
+        map: map_file(map.unwrap_or(DEFAULT_MAP.into())),
+        output: output.unwrap_or("map.png".into()),
+        rect: rect,
+        overlays: overlays,
+    })
+}
+
+fn parse_dump(args: &mut Args) -> Result<Command, String> {
+    let mut map = None;
+    let mut format = None;
+    let mut output = None;
+    let mut event = None;
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--format" => {
+                format = Some(match &args.value("--format")?[..] {
+                    "text" => DumpFormat::Text,
+                    "json" => DumpFormat::Json,
+                    "tmx" => DumpFormat::Tmx,
+                    other => return Err(format!("unknown dump format {:?}", other)),
+                })
+            }
+            "-o" | "--output" => output = Some(args.value("--output")?),
+            "--event" => event = Some(args.value("--event")?),
+            _ if is_help(&arg) => return Ok(Command::Help),
+            _ if map.is_none() && !arg.starts_with('-') => map = Some(arg.clone()),
+            _ => return Err(args.unexpected(&arg)),
+        }
+    }
+    let format = match (format, event.is_some()) {
+        (None, true) | (Some(DumpFormat::Json), true) => DumpFormat::Json,
+        (Some(_), true) => return Err("events can only be dumped as JSON".to_owned()),
+        (format, false) => format.unwrap_or(DumpFormat::Text),
+    };
+    Ok(Command::Dump {
+        map: map_file(map.unwrap_or(DEFAULT_MAP.into())),
+        format: format,
+        output: output.unwrap_or(".".into()),
+        event: event,
+    })
+}
+
+fn parse_events(args: &mut Args) -> Result<Command, String> {
+    let mut map = None;
+    let mut settings = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--set" => settings.push(args.value("--set")?),
+            _ if is_help(&arg) => return Ok(Command::Help),
+            _ if map.is_none() && !arg.starts_with('-') => map = Some(arg.clone()),
+            _ => return Err(args.unexpected(&arg)),
+        }
+    }
+    Ok(Command::Events {
+        map: map_file(map.unwrap_or(DEFAULT_MAP.into())),
+        settings: settings,
+    })
+}
+
+fn parse_tiles(args: &mut Args) -> Result<Command, String> {
+    let mut map = None;
+    let mut layer = None;
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--layer" => layer = Some(args.value("--layer")?),
+            _ if is_help(&arg) => return Ok(Command::Help),
+            _ if map.is_none() && !arg.starts_with('-') => map = Some(arg.clone()),
+            _ => return Err(args.unexpected(&arg)),
+        }
+    }
+    Ok(Command::Tiles {
+        map: map_file(map.unwrap_or(DEFAULT_MAP.into())),
+        layer: layer,
+    })
+}
+
+/// Parse the program's arguments, not including the program name. Global
+/// options come before the command. With no command, the default map is
+/// viewed, and a first argument that isn't a command is the map to view.
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut assets = None;
+    let mut prefer_png = false;
+    let mut args = Args {
+        args: args.collect::<Vec<_>>().into_iter().peekable(),
+        command: "the program",
+    };
+    loop {
+        match args.peek() {
+            Some("-h") | Some("--help") => {
+                return Ok(Options {
+                    assets: None,
+                    prefer_png: false,
+                    command: Command::Help,
+                })
+            }
+            Some("--prefer-png") => prefer_png = true,
+            Some("--assets") => {
+                let _ = args.next();
+                assets = Some(args.value("--assets")?);
+                continue;
+            }
+            _ => break,
+        }
+        let _ = args.next();
+    }
+
+    let (command, named): (&'static str, bool) = match args.peek() {
+        None => ("view", false),
+        Some("view") => ("view", true),
+        Some("render") => ("render", true),
+        Some("dump") => ("dump", true),
+        Some("events") => ("events", true),
+        Some("validate") => ("validate", true),
+        Some("tiles") => ("tiles", true),
+        Some("help") => ("help", true),
+        // `spinning-square Town.xnb` views the map, as it always has.
+        Some(other) if !other.starts_with('-') => ("view", false),
+        Some(other) => return Err(format!("unknown option {:?}", other)),
+    };
+    if named {
+        let _ = args.next();
+    }
+    args.command = command;
+    let command = match command {
+        "view" => parse_view(&mut args)?,
+        "render" => parse_render(&mut args)?,
+        "dump" => parse_dump(&mut args)?,
+        "events" => parse_events(&mut args)?,
+        "validate" => match args.next() {
+            Some(ref arg) if is_help(arg) => Command::Help,
+            Some(arg) => return Err(args.unexpected(&arg)),
+            None => Command::Validate,
+        },
+        "tiles" => parse_tiles(&mut args)?,
+        "help" => Command::Help,
+        _ => unreachable!(),
+    };
+    Ok(Options {
+        assets: assets,
+        prefer_png: prefer_png,
+        command: command,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn bare_map_is_viewed() {
+        match parse_args(&["--prefer-png", "Farm"]).unwrap() {
+            Options { prefer_png: true, command: Command::View { ref map, .. }, .. } => assert_eq!(map, "Farm.xnb"),
+            _ => panic!("expected to view Farm"),
+        }
+    }
+
+    #[test]
+    fn global_options_come_before_the_command() {
+        match parse_args(&["--assets", "content", "validate"]).unwrap() {
+            Options { ref assets, command: Command::Validate, .. } => {
+                assert_eq!(assets.as_ref().map(|s| &s[..]), Some("content"))
+            }
+            _ => panic!("expected validate"),
+        }
+        assert!(parse_args(&["dump", "--output", "--assets", "content"]).is_err());
+        assert!(parse_args(&["validate", "--prefer-png"]).is_err());
+        assert!(parse_args(&["--assets", "--prefer-png", "validate"]).is_err());
+    }
+
+    #[test]
+    fn help_after_a_command_shows_usage() {
+        for args in &[&["render", "--help"][..], &["dump", "Farm", "-h"][..], &["validate", "--help"][..]] {
+            match parse_args(args).unwrap().command {
+                Command::Help => {}
+                _ => panic!("expected help for {:?}", args),
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use font::Font;
+use graphics::{Context, Transformed, rectangle};
+use opengl_graphics::GlGraphics;
+use piston::input::Key;
+
+const MARGIN: f64 = 16.;
+const PADDING: f64 = 12.;
+const TEXT_SCALE: f64 = 1.5;
+const BORDER: [f32; 4] = [0.36, 0.2, 0.09, 1.0];
+const BACKGROUND: [f32; 4] = [0.98, 0.84, 0.58, 0.95];
+const TEXT: [f32; 4] = [0.25, 0.13, 0.05, 1.0];
+const HIGHLIGHT: [f32; 4] = [0.85, 0.25, 0.1, 1.0];
+
+/// Remove the portrait and control codes (`$h`, `$s`, `$e`, ...) from a page
+/// of dialogue and substitute the farmer's name for `@`.
+fn clean_page(page: &str) -> String {
+    let mut cleaned = String::new();
+    let mut chars = page.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => {
+                while chars.peek().map_or(false, |c| c.is_alphanumeric()) {
+                    let _ = chars.next();
+                }
+            }
+            '@' => cleaned.push_str("Farmer"),
+            '#' => cleaned.push(' '),
+            c => cleaned.push(c),
+        }
+    }
+    cleaned.trim().to_owned()
+}
+
+/// Split dialogue on its `#$b#` (and `#$e#`) page breaks.
+pub fn split_pages(text: &str) -> Vec<String> {
+    text.split("#$b#")
+        .flat_map(|p| p.split("#$e#"))
+        .map(clean_page)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Break `text` into lines no wider than `width` pixels.
+fn wrap(font: &Font, text: &str, width: f64) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if !line.is_empty() && font.measure(&candidate) > width {
+            lines.push(line);
+            line = word.to_owned();
+        } else {
+            line = candidate;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+pub enum DialogueAction {
+    /// The dialogue is still showing.
+    Continue,
+    /// The last page was dismissed.
+    Finished,
+    /// A question's answer was chosen.
+    Answered(usize),
+}
+
+/// A box at the bottom of the screen showing speech, messages or questions.
+pub struct DialogueBox {
+    speaker: Option<String>,
+    pages: Vec<String>,
+    page: usize,
+    choices: Vec<String>,
+    selected: usize,
+}
+
+impl DialogueBox {
+    pub fn speech(speaker: &str, text: &str) -> DialogueBox {
+        DialogueBox {
+            speaker: Some(speaker.to_owned()),
+            pages: split_pages(text),
+            page: 0,
+            choices: vec![],
+            selected: 0,
+        }
+    }
+
+    pub fn message(text: &str) -> DialogueBox {
+        DialogueBox {
+            speaker: None,
+            pages: split_pages(text),
+            page: 0,
+            choices: vec![],
+            selected: 0,
+        }
+    }
+
+    /// A question, written as `question#answer 1#answer 2...`.
+    pub fn question(text: &str) -> DialogueBox {
+        let mut parts = text.split('#');
+        let question = parts.next().unwrap_or("");
+        DialogueBox {
+            speaker: None,
+            pages: vec![clean_page(question)],
+            page: 0,
+            choices: parts.map(clean_page).collect(),
+            selected: 0,
+        }
+    }
+
+    fn on_last_page(&self) -> bool {
+        self.page + 1 >= self.pages.len()
+    }
+
+    pub fn key_pressed(&mut self, key: Key) -> DialogueAction {
+        let showing_choices = self.on_last_page() && !self.choices.is_empty();
+        match key {
+            Key::Up | Key::W if showing_choices && self.selected > 0 => self.selected -= 1,
+            Key::Down | Key::S if showing_choices && self.selected + 1 < self.choices.len() => {
+                self.selected += 1
+            }
+            Key::Return | Key::Space => {
+                if !self.on_last_page() {
+                    self.page += 1;
+                } else if showing_choices {
+                    return DialogueAction::Answered(self.selected);
+                } else {
+                    return DialogueAction::Finished;
+                }
+            }
+            _ => {}
+        }
+        DialogueAction::Continue
+    }
+
+    pub fn draw(&self, font: &Font, c: Context, gl: &mut GlGraphics) {
+        let size = c.get_view_size();
+        let (width, height) = (size[0], size[1]);
+        let text_width = width - 2. * (MARGIN + PADDING);
+        let line_height = font.line_height() * TEXT_SCALE;
+
+        let text = self.pages.get(self.page).map_or("", |p| &p[..]);
+        let mut lines = wrap(font, text, text_width / TEXT_SCALE);
+        if self.on_last_page() {
+            for (i, choice) in self.choices.iter().enumerate() {
+                let marker = if i == self.selected { "> " } else { "  " };
+                lines.push(format!("{}{}", marker, choice));
+            }
+        }
+        let name_lines = if self.speaker.is_some() { 1. } else { 0. };
+        let box_height = (lines.len() as f64 + name_lines) * line_height + 2. * PADDING;
+        let top = height - MARGIN - box_height;
+
+        rectangle(BORDER, [MARGIN - 3., top - 3., width - 2. * MARGIN + 6., box_height + 6.], c.transform, gl);
+        rectangle(BACKGROUND, [MARGIN, top, width - 2. * MARGIN, box_height], c.transform, gl);
+
+        let transform = c.transform.trans(MARGIN + PADDING, top + PADDING);
+        let mut y = 0.;
+        if let Some(ref speaker) = self.speaker {
+            font.draw(speaker, (0., y), TEXT_SCALE, BORDER, transform, gl);
+            y += line_height;
+        }
+        let choice_start = lines.len() - if self.on_last_page() { self.choices.len() } else { 0 };
+        for (i, line) in lines.iter().enumerate() {
+            let color = if i >= choice_start && i - choice_start == self.selected {
+                HIGHLIGHT
+            } else {
+                TEXT
+            };
+            font.draw(line, (0., y), TEXT_SCALE, color, transform, gl);
+            y += line_height;
+        }
+    }
+}
+
+/// Short-lived text floating above an actor, from `textAboveHead`.
+pub struct TextAboveHead {
+    pub actor: String,
+    pub text: String,
+    pub expires: u32,
+}
+
+impl TextAboveHead {
+    /// Draw the text centered above the map pixel position `pos`, where
+    /// `transform` maps map pixels to the screen.
+    pub fn draw(&self, font: &Font, pos: (f64, f64), transform: [[f64; 3]; 2], gl: &mut GlGraphics) {
+        let width = font.measure(&self.text);
+        let x = pos.0 + 8. - width / 2.;
+        let y = pos.1 - 32. - font.line_height();
+        rectangle([0.0, 0.0, 0.0, 0.5], [x - 2., y - 1., width + 4., font.line_height() + 2.], transform, gl);
+        font.draw(&self.text, (x, y), 1., [1.0, 1.0, 1.0, 1.0], transform, gl);
+    }
+}
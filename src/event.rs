@@ -0,0 +1,724 @@
+use assets::{AssetError, AssetSource};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use trigger::{GameState, Trigger};
+use xnb::{XNB, Dictionary};
+
+pub struct ScriptedCharacter {
+    pub name: String,
+    pub pos: (i32, i32),
+    pub dir: u8,
+}
+
+pub enum Command {
+    Pause(u32),
+    Emote(String, u8),
+    Move(String, (i32, i32), u8),
+    Speak(String, String),
+    GlobalFade,
+    Viewport(i32, i32),
+    Warp(String, (i32, i32)),
+    FaceDirection(String, u8),
+    ShowFrame(String, u32),
+    Speed(String, u8),
+    PlaySound(String),
+    Shake(String, u32),
+    Jump(String),
+    TextAboveHead(String, String),
+    AddQuest(u32),
+    Message(String),
+    Animate(String, bool, bool, u32, Vec<u32>),
+    StopAnimation(String),
+    Mail(String),
+    Friendship(String, i32),
+    PlayMusic(String),
+    SpecificTemporarySprite(String),
+    ChangeLocation(String),
+    ChangeToTemporaryMap(String),
+    Question(String, String),
+    Fork(String),
+    AmbientLight(u32, u32, u32),
+    PositionOffset(String, i32, i32),
+    /// A command that isn't understood, or whose arguments couldn't be
+    /// parsed, recorded by `ParseMode::Lenient`.
+    Unknown(Vec<String>),
+}
+
+/// What happens once an event's commands have all run.
+pub enum End {
+    /// `end warpOut`: the farmer leaves the location through its first warp.
+    WarpOut,
+    /// `end dialogue <npc> "<text>"`: the NPC's dialogue changes to this text.
+    Dialogue(String, String),
+    /// `end dialogueWarpOut <npc> "<text>"`
+    DialogueWarpOut(String, String),
+    /// `end position <x> <y>`: the farmer is left at this tile.
+    Position((u32, u32)),
+    /// `end newDay`: the day ends. The viewer has no calendar, so this ends
+    /// the event like a bare `end`.
+    NewDay,
+    /// `end invisible <npc>`: the NPC is hidden for the rest of the day.
+    Invisible(String),
+    /// `end invisibleWarpOut <npc>`
+    InvisibleWarpOut(String),
+    /// A bare `end`, or no end clause at all.
+    End,
+    /// Any other end clause.
+    Other(Vec<String>),
+}
+
+pub struct ScriptedEvent {
+    pub id: String,
+    pub music: String,
+    pub viewport: (i32, i32),
+    pub characters: Vec<ScriptedCharacter>,
+    pub skippable: bool,
+    pub commands: Vec<Command>,
+    pub end: End,
+    pub triggers: Vec<Trigger>,
+    pub forks: Vec<ScriptedEvent>,
+}
+
+impl ScriptedEvent {
+    /// Find the fork script named `name` anywhere beneath this event.
+    pub fn find_fork(&self, name: &str) -> Option<&ScriptedEvent> {
+        for fork in &self.forks {
+            if fork.id == name {
+                return Some(fork);
+            }
+            if let Some(fork) = fork.find_fork(name) {
+                return Some(fork);
+            }
+        }
+        None
+    }
+
+    /// Whether all of this event's preconditions hold in `state`.
+    pub fn would_fire(&self, state: &GameState) -> bool {
+        self.triggers.iter().all(|t| t.is_satisfied(state))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventParseErrorKind {
+    /// The event is missing its music, viewport or character section.
+    MissingSection(&'static str),
+    /// The argument at this position was not present.
+    MissingArgument(usize),
+    /// The argument at this position could not be parsed.
+    InvalidArgument(usize),
+    UnknownCommand,
+    /// A `fork` names a script that isn't in the events dictionary.
+    MissingFork,
+}
+
+#[derive(Debug)]
+pub struct EventParseError {
+    pub event_id: String,
+    /// The index of the failing command, or `None` if the failure was in
+    /// the music/viewport/character header.
+    pub command_index: Option<usize>,
+    pub command: String,
+    pub argument: Option<String>,
+    pub kind: EventParseErrorKind,
+}
+
+impl fmt::Display for EventParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "event {}", self.event_id)?;
+        if let Some(index) = self.command_index {
+            write!(f, ", command {}", index)?;
+        }
+        write!(f, " ({:?}): ", self.command)?;
+        match self.kind {
+            EventParseErrorKind::MissingSection(section) => write!(f, "missing {}", section),
+            EventParseErrorKind::MissingArgument(i) => write!(f, "missing argument {}", i),
+            EventParseErrorKind::InvalidArgument(i) =>
+                write!(f, "invalid argument {}: {:?}", i, self.argument.as_ref().map_or("", |s| &s[..])),
+            EventParseErrorKind::UnknownCommand => write!(f, "unknown command"),
+            EventParseErrorKind::MissingFork => write!(f, "no script for fork"),
+        }
+    }
+}
+
+impl Error for EventParseError {
+    fn description(&self) -> &str {
+        "failed to parse event script"
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ParseMode {
+    /// Fail on any command that isn't understood.
+    Strict,
+    /// Record commands that aren't understood, or whose arguments can't be
+    /// parsed, as `Command::Unknown`.
+    Lenient,
+}
+
+/// Split a command on spaces, treating a double-quoted string as a single
+/// argument (without its quotes).
+fn split_args(text: &str) -> Vec<&str> {
+    let mut args = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ' ' if !in_quotes => {
+                args.push(text[start..i].trim_matches('"'));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(text[start..].trim_matches('"'));
+    args
+}
+
+/// The space-separated pieces of a single command or header section, along
+/// with enough context to report where parsing went wrong.
+struct Args<'a> {
+    event_id: &'a str,
+    command_index: Option<usize>,
+    text: &'a str,
+    args: Vec<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn new(event_id: &'a str, command_index: Option<usize>, text: &'a str) -> Args<'a> {
+        Args {
+            event_id: event_id,
+            command_index: command_index,
+            text: text,
+            args: split_args(text),
+        }
+    }
+
+    fn error(&self, kind: EventParseErrorKind, argument: Option<&str>) -> EventParseError {
+        EventParseError {
+            event_id: self.event_id.to_owned(),
+            command_index: self.command_index,
+            command: self.text.to_owned(),
+            argument: argument.map(|s| s.to_owned()),
+            kind: kind,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    fn get(&self, i: usize) -> Result<&'a str, EventParseError> {
+        match self.args.get(i) {
+            Some(s) => Ok(*s),
+            None => Err(self.error(EventParseErrorKind::MissingArgument(i), None)),
+        }
+    }
+
+    fn string(&self, i: usize) -> Result<String, EventParseError> {
+        self.get(i).map(|s| s.to_owned())
+    }
+
+    fn parse<T: FromStr>(&self, i: usize) -> Result<T, EventParseError> {
+        let s = self.get(i)?;
+        s.parse().map_err(|_| self.error(EventParseErrorKind::InvalidArgument(i), Some(s)))
+    }
+
+    fn parse_from<T: FromStr>(&self, start: usize) -> Result<Vec<T>, EventParseError> {
+        (start..self.len()).map(|i| self.parse(i)).collect()
+    }
+
+    fn strings_from(&self, start: usize) -> Result<Vec<String>, EventParseError> {
+        (start..self.len()).map(|i| self.string(i)).collect()
+    }
+
+    fn flag(&self, i: usize) -> Result<bool, EventParseError> {
+        self.get(i).map(|s| s == "t")
+    }
+}
+
+fn parse_end(args: &Args) -> Result<End, EventParseError> {
+    if args.len() == 1 {
+        return Ok(End::End);
+    }
+    Ok(match args.get(1)? {
+        "warpOut" => End::WarpOut,
+        "dialogue" => End::Dialogue(args.string(2)?, args.string(3)?),
+        "dialogueWarpOut" => End::DialogueWarpOut(args.string(2)?, args.string(3)?),
+        "position" => End::Position((args.parse(2)?, args.parse(3)?)),
+        "newDay" => End::NewDay,
+        "invisible" => End::Invisible(args.string(2)?),
+        "invisibleWarpOut" => End::InvisibleWarpOut(args.string(2)?),
+        _ => End::Other(args.strings_from(1)?),
+    })
+}
+
+fn parse_command(args: &Args) -> Result<Option<Command>, EventParseError> {
+    Ok(Some(match args.get(0)? {
+        "pause" => Command::Pause(args.parse(1)?),
+        "emote" => Command::Emote(args.string(1)?, args.parse(2)?),
+        "move" => Command::Move(args.string(1)?,
+                                (args.parse(2)?, args.parse(3)?),
+                                args.parse(4)?),
+        "speak" => Command::Speak(args.string(1)?, args.string(2)?),
+        "globalFade" => Command::GlobalFade,
+        "viewport" => Command::Viewport(args.parse(1)?, args.parse(2)?),
+        "warp" => Command::Warp(args.string(1)?,
+                                (args.parse(2)?, args.parse(3)?)),
+        "faceDirection" => Command::FaceDirection(args.string(1)?, args.parse(2)?),
+        "showFrame" => Command::ShowFrame(args.string(1)?, args.parse(2)?),
+        "speed" => Command::Speed(args.string(1)?, args.parse(2)?),
+        "playSound" => Command::PlaySound(args.string(1)?),
+        "shake" => Command::Shake(args.string(1)?, args.parse(2)?),
+        "jump" => Command::Jump(args.string(1)?),
+        "textAboveHead" => Command::TextAboveHead(args.string(1)?, args.string(2)?),
+        "addQuest" => Command::AddQuest(args.parse(1)?),
+        "message" => Command::Message(args.string(1)?),
+        "animate" => Command::Animate(args.string(1)?,
+                                      args.flag(2)?,
+                                      args.flag(3)?,
+                                      args.parse(4)?,
+                                      args.parse_from(5)?),
+        "stopAnimation" => Command::StopAnimation(args.string(1)?),
+        "mail" => Command::Mail(args.string(1)?),
+        "friendship" => Command::Friendship(args.string(1)?, args.parse(2)?),
+        "playMusic" => Command::PlayMusic(args.string(1)?),
+        "specificTemporarySprite" => Command::SpecificTemporarySprite(args.string(1)?),
+        "changeLocation" => Command::ChangeLocation(args.string(1)?),
+        "changeToTemporaryMap" => Command::ChangeToTemporaryMap(args.string(1)?),
+        "question" => Command::Question(args.string(1)?, args.string(2)?),
+        "fork" => Command::Fork(args.string(1)?),
+        "ambientLight" => Command::AmbientLight(args.parse(1)?,
+                                                args.parse(2)?,
+                                                args.parse(3)?),
+        "positionOffset" => Command::PositionOffset(args.string(1)?,
+                                                    args.parse(2)?,
+                                                    args.parse(3)?),
+        "" => return Ok(None),
+        _ => return Err(args.error(EventParseErrorKind::UnknownCommand, None)),
+    }))
+}
+
+fn parse_trigger(args: &Args) -> Result<Trigger, EventParseError> {
+    Ok(match args.get(0)? {
+        "d" => Trigger::NotDayOfWeek(args.parse_from(1)?),
+        "z" => Trigger::NotSeason(args.parse_from(1)?),
+        "u" => Trigger::DayOfMonth(args.parse_from(1)?),
+        "y" => Trigger::Year(args.parse(1)?),
+        "t" => Trigger::TimeRange(args.parse(1)?, args.parse(2)?),
+        "w" => Trigger::Weather(args.parse(1)?),
+        "f" => {
+            let mut npcs = vec![];
+            for i in 0..(args.len() - 1) / 2 {
+                npcs.push((args.string(i * 2 + 1)?, args.parse(i * 2 + 2)?));
+            }
+            Trigger::Friendship(npcs)
+        }
+        "e" => Trigger::SeenEvents(args.strings_from(1)?),
+        "k" => Trigger::NotSeenEvents(args.strings_from(1)?),
+        "n" => Trigger::Mail(args.string(1)?),
+        "l" => Trigger::NotMail(args.string(1)?),
+        "j" => Trigger::DaysPlayed(args.parse(1)?),
+        "m" => Trigger::MoneyEarned(args.parse(1)?),
+        "O" => Trigger::MarriedTo(args.string(1)?),
+        "D" => Trigger::Dating(args.string(1)?),
+        "p" => Trigger::NpcPresent(args.string(1)?),
+        "r" => Trigger::Random(args.parse(1)?),
+        _ => Trigger::Unknown(args.strings_from(0)?),
+    })
+}
+
+/// Parse the precondition part of an event key (everything after the id).
+pub fn parse_triggers(key: &str) -> Result<Vec<Trigger>, EventParseError> {
+    let id = key.split('/').next().unwrap_or("");
+    key.split('/')
+        .skip(1)
+        .filter(|s| !s.is_empty())
+        .map(|s| parse_trigger(&Args::new(id, None, s)))
+        .collect()
+}
+
+/// Parse an event script. `key` is the event's key in the events dictionary,
+/// which may include preconditions after the id.
+pub fn parse_script(key: String, s: String, mode: ParseMode) -> Result<ScriptedEvent, EventParseError> {
+    let triggers = parse_triggers(&key)?;
+    let id = key.split('/').next().unwrap_or("").to_owned();
+    let mut lines = s.lines();
+    let mut parts = lines.next().unwrap_or("").split('/');
+    let missing = |section| {
+        Args::new(&id, None, &s).error(EventParseErrorKind::MissingSection(section), None)
+    };
+
+    let music = parts.next().ok_or_else(|| missing("music"))?.to_owned();
+    let viewport_str = parts.next().ok_or_else(|| missing("viewport"))?;
+    let viewport_args = Args::new(&id, None, viewport_str);
+    let viewport = (viewport_args.parse(0)?, viewport_args.parse(1)?);
+
+    let character_str = parts.next().ok_or_else(|| missing("characters"))?;
+    let character_args = Args::new(&id, None, character_str);
+    if character_args.len() % 4 != 0 {
+        let missing = character_args.len();
+        return Err(character_args.error(EventParseErrorKind::MissingArgument(missing), None));
+    }
+    let mut characters = vec![];
+    for i in 0..character_args.len() / 4 {
+        let character = ScriptedCharacter {
+            name: character_args.string(i * 4)?,
+            pos: (character_args.parse(i * 4 + 1)?,
+                  character_args.parse(i * 4 + 2)?),
+            dir: character_args.parse(i * 4 + 3)?,
+        };
+        characters.push(character);
+    }
+
+    let mut peekable = parts.peekable();
+    let skippable = match peekable.peek() {
+        Some(&"skippable") => {
+            let _ = peekable.next();
+            true
+        }
+        Some(_) | None => false,
+    };
+
+    let (commands, end) = parse_commands(&id, peekable, mode)?;
+
+    let mut event = ScriptedEvent {
+        id: id,
+        music: music,
+        viewport: viewport,
+        characters: characters,
+        skippable: skippable,
+        commands: commands,
+        end: end,
+        triggers: triggers,
+        forks: vec![],
+    };
+
+    // Any further lines are fork scripts named by their first field, such as
+    // `Declined/speak Abigail "Oh."/end`, for `fork Declined` to switch to.
+    for section in lines {
+        let mut fields = section.splitn(2, '/');
+        let name = fields.next().unwrap_or("").trim();
+        if name.is_empty() {
+            continue;
+        }
+        let fork = parse_fork(name.to_owned(), fields.next().unwrap_or(""), &event, mode)?;
+        event.forks.push(fork);
+    }
+
+    Ok(event)
+}
+
+/// Parse a sequence of commands up to and including the `end` clause, which
+/// stops parsing.
+fn parse_commands<'a, I>(id: &str, parts: I, mode: ParseMode) -> Result<(Vec<Command>, End), EventParseError>
+    where I: Iterator<Item = &'a str>
+{
+    let mut commands = vec![];
+    for (i, command_str) in parts.enumerate() {
+        let args = Args::new(id, Some(i), command_str);
+        if args.get(0)? == "end" {
+            let end = match parse_end(&args) {
+                Err(_) if mode == ParseMode::Lenient => End::Other(args.strings_from(1)?),
+                end => end?,
+            };
+            return Ok((commands, end));
+        }
+        match parse_command(&args) {
+            Ok(Some(command)) => commands.push(command),
+            Ok(None) => (),
+            Err(_) if mode == ParseMode::Lenient => {
+                commands.push(Command::Unknown(args.args.iter().map(|s| s.to_string()).collect()));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((commands, End::End))
+}
+
+/// Parse a fork script on its own. Fork scripts contain only commands, so
+/// the result has no music, viewport or characters.
+pub fn parse_fork_script(id: String, s: &str, mode: ParseMode) -> Result<ScriptedEvent, EventParseError> {
+    let (commands, end) = parse_commands(&id, s.split('/'), mode)?;
+    Ok(ScriptedEvent {
+        id: id,
+        music: String::new(),
+        viewport: (0, 0),
+        characters: vec![],
+        skippable: false,
+        commands: commands,
+        end: end,
+        triggers: vec![],
+        forks: vec![],
+    })
+}
+
+/// Parse an alternate script for `parent`. The music, viewport and
+/// characters carry over from the parent.
+fn parse_fork(id: String, s: &str, parent: &ScriptedEvent, mode: ParseMode)
+              -> Result<ScriptedEvent, EventParseError> {
+    let mut fork = parse_fork_script(id, s, mode)?;
+    fork.music = parent.music.clone();
+    fork.viewport = parent.viewport;
+    fork.skippable = parent.skippable;
+    Ok(fork)
+}
+
+/// The names of the scripts that `fork` commands anywhere in an events
+/// dictionary switch to.
+pub fn fork_targets(sources: &HashMap<String, String>) -> HashSet<String> {
+    let mut targets = HashSet::new();
+    for source in sources.values() {
+        for command in source.split(|c| c == '/' || c == '\n') {
+            let args = split_args(command);
+            if args[0] == "fork" && args.len() > 1 {
+                targets.insert(args[1].to_owned());
+            }
+        }
+    }
+    targets
+}
+
+/// Whether `key` is an event header: a numeric id, optionally followed by
+/// preconditions.
+fn is_event_header(key: &str) -> bool {
+    key.split('/').next().map_or(false, |id| id.parse::<i32>().is_ok())
+}
+
+/// The keys of the events in an events dictionary, in sorted order. Fork
+/// scripts and any other entries without an event header are left out.
+pub fn event_keys(sources: &HashMap<String, String>) -> Vec<String> {
+    let forks = fork_targets(sources);
+    let mut keys: Vec<_> = sources.keys()
+        .filter(|key| is_event_header(key) && !forks.contains(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Parse the fork scripts named by `fork` commands in `event` from the
+/// events dictionary, recursively.
+fn resolve_forks(event: &mut ScriptedEvent,
+                 sources: &HashMap<String, String>,
+                 mode: ParseMode,
+                 visited: &mut HashSet<String>) -> Result<(), EventParseError> {
+    let names: Vec<String> = event.commands.iter().filter_map(|c| match *c {
+        Command::Fork(ref name) => Some(name.clone()),
+        _ => None,
+    }).collect();
+
+    for name in names {
+        if visited.contains(&name) || event.forks.iter().any(|f| f.id == name) {
+            continue;
+        }
+        visited.insert(name.clone());
+        let fork = match sources.get(&name) {
+            Some(source) => parse_fork(name.clone(), source, event, mode)?,
+            None if mode == ParseMode::Lenient => continue,
+            None => return Err(EventParseError {
+                event_id: event.id.clone(),
+                command_index: None,
+                command: format!("fork {}", name),
+                argument: Some(name.clone()),
+                kind: EventParseErrorKind::MissingFork,
+            }),
+        };
+        event.forks.push(fork);
+    }
+
+    for fork in &mut event.forks {
+        resolve_forks(fork, sources, mode, visited)?;
+    }
+    Ok(())
+}
+
+/// Parse the event with `key` from an events dictionary, along with every
+/// fork script it can reach.
+pub fn parse_event(key: &str,
+                   sources: &HashMap<String, String>,
+                   mode: ParseMode) -> Result<ScriptedEvent, EventParseError> {
+    let source = sources.get(key).cloned().unwrap_or_default();
+    let mut event = parse_script(key.to_owned(), source, mode)?;
+    // The event's own sections take precedence over dictionary entries.
+    let mut visited: HashSet<String> = event.forks.iter().map(|f| f.id.clone()).collect();
+    visited.insert(event.id.clone());
+    resolve_forks(&mut event, sources, mode, &mut visited)?;
+    Ok(event)
+}
+
+/// Why an events dictionary or an event in it couldn't be loaded.
+#[derive(Debug)]
+pub enum EventLoadError {
+    Missing(AssetError),
+    /// The dictionary file couldn't be read.
+    Invalid(PathBuf, String),
+    Parse(EventParseError),
+}
+
+impl fmt::Display for EventLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EventLoadError::Missing(ref e) => write!(f, "{}", e),
+            EventLoadError::Invalid(ref path, ref e) =>
+                write!(f, "couldn't read events {}: {}", path.display(), e),
+            EventLoadError::Parse(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for EventLoadError {
+    fn description(&self) -> &str {
+        match *self {
+            EventLoadError::Missing(_) => "events not found",
+            EventLoadError::Invalid(..) => "invalid events file",
+            EventLoadError::Parse(ref e) => e.description(),
+        }
+    }
+}
+
+impl From<AssetError> for EventLoadError {
+    fn from(e: AssetError) -> EventLoadError {
+        EventLoadError::Missing(e)
+    }
+}
+
+impl From<EventParseError> for EventLoadError {
+    fn from(e: EventParseError) -> EventLoadError {
+        EventLoadError::Parse(e)
+    }
+}
+
+/// Load the raw event dictionary from `Data/Events` for `map_name`, keyed by
+/// the event id and precondition string. Maps loaded from `.tmx` or `.tbin`
+/// files share the events of the XNB map with the same name.
+pub fn load_event_sources(assets: &AssetSource, map_name: &str) -> Result<HashMap<String, String>, EventLoadError> {
+    let path = Path::new("Data/Events").join(Path::new(map_name).with_extension("xnb"));
+    let mut f = assets.open(&path)?;
+    let xnb = XNB::<Dictionary<String, String>>::from_buffer(&mut f)
+        .map_err(|e| EventLoadError::Invalid(path.clone(), format!("{:?}", e)))?;
+    Ok(xnb.primary.map)
+}
+
+/// Look up the event `id` in the `Data/Events` dictionary for `map_name`.
+/// Returns `None` if the dictionary has no such event.
+pub fn load_event(assets: &AssetSource,
+                  map_name: &str,
+                  id: &str,
+                  mode: ParseMode) -> Result<Option<ScriptedEvent>, EventLoadError> {
+    let sources = load_event_sources(assets, map_name)?;
+    let key = sources.keys().find(|k| k.split('/').next() == Some(id)).cloned();
+    match key {
+        Some(key) => Ok(Some(parse_event(&key, &sources, mode)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trigger::Weather;
+
+    #[test]
+    fn split_args_keeps_quoted_strings_together() {
+        assert_eq!(split_args("pause 500"), ["pause", "500"]);
+        assert_eq!(split_args(r#"speak Abigail "Hi there, @!""#), ["speak", "Abigail", "Hi there, @!"]);
+        assert_eq!(split_args(r#"question fork1 "Go?#Yes#No" "#), ["question", "fork1", "Go?#Yes#No", ""]);
+        assert_eq!(split_args(""), [""]);
+    }
+
+    #[test]
+    fn triggers_are_parsed_from_the_key() {
+        let triggers = parse_triggers("1590166/f Abigail 1000 Sam 500/t 600 1200/z winter fall/w rainy/x y").unwrap();
+        assert_eq!(triggers.len(), 5);
+        match triggers[0] {
+            Trigger::Friendship(ref npcs) => {
+                assert_eq!(npcs[..], [("Abigail".to_owned(), 1000), ("Sam".to_owned(), 500)])
+            }
+            _ => panic!("expected friendship"),
+        }
+        match triggers[1] {
+            Trigger::TimeRange(600, 1200) => {}
+            _ => panic!("expected a time range"),
+        }
+        match triggers[2] {
+            Trigger::NotSeason(ref seasons) => assert_eq!(seasons.len(), 2),
+            _ => panic!("expected seasons"),
+        }
+        match triggers[4] {
+            Trigger::Unknown(ref args) => assert_eq!(args[..], ["x", "y"]),
+            _ => panic!("expected an unknown trigger"),
+        }
+
+        let mut state = GameState::default();
+        state.time = 900;
+        state.weather = Weather::Rainy;
+        state.friendship.insert("Abigail".to_owned(), 1000);
+        state.friendship.insert("Sam".to_owned(), 500);
+        assert!(triggers[..4].iter().all(|t| t.is_satisfied(&state)));
+        assert!(!triggers[4].is_satisfied(&state));
+
+        let e = parse_triggers("1/t 600 noon").err().unwrap();
+        assert_eq!(e.kind, EventParseErrorKind::InvalidArgument(2));
+    }
+
+    #[test]
+    fn lenient_mode_records_bad_arguments() {
+        let script = "none/0 0/farmer 1 1 2/viewport move 1 0 2000/move farmer 0 1 2 true/end".to_owned();
+        assert!(parse_script("1".to_owned(), script.clone(), ParseMode::Strict).is_err());
+
+        let event = parse_script("1".to_owned(), script, ParseMode::Lenient).unwrap();
+        assert_eq!(event.commands.len(), 2);
+        match event.commands[0] {
+            Command::Unknown(ref args) => assert_eq!(args[..], ["viewport", "move", "1", "0", "2000"]),
+            _ => panic!("expected an unknown command"),
+        }
+    }
+
+    #[test]
+    fn event_keys_skip_fork_scripts() {
+        let mut sources = HashMap::new();
+        sources.insert("100/f Abigail 250".to_owned(),
+                       "none/0 0/farmer 1 1 2/question fork0 \"Yes?#Yes#No\"/fork Declined/end".to_owned());
+        sources.insert("Declined".to_owned(), "pause 500/end".to_owned());
+        sources.insert("Unused".to_owned(), "pause 500/end".to_owned());
+
+        assert_eq!(fork_targets(&sources).into_iter().collect::<Vec<_>>(), ["Declined"]);
+        assert_eq!(event_keys(&sources), ["100/f Abigail 250"]);
+    }
+
+    #[test]
+    fn forks_are_resolved_by_name() {
+        let mut sources = HashMap::new();
+        sources.insert("100".to_owned(),
+                       "none/0 0/farmer 1 1 2/fork Declined/end\nLater/pause 100/fork Again/end".to_owned());
+        sources.insert("Declined".to_owned(), "fork Again/fork Later/end warpOut".to_owned());
+        sources.insert("Again".to_owned(), "pause 200/fork Declined/end".to_owned());
+
+        let event = parse_event("100", &sources, ParseMode::Strict).unwrap();
+        assert_eq!(event.commands.len(), 1);
+        assert_eq!(event.forks.len(), 2);
+        assert_eq!(event.forks[0].id, "Later");
+        assert_eq!(event.forks[0].commands.len(), 2);
+        assert_eq!(event.forks[0].music, "none");
+        assert_eq!(event.forks[1].id, "Declined");
+        match event.forks[1].end {
+            End::WarpOut => {}
+            _ => panic!("expected the fork's own end clause"),
+        }
+        assert_eq!(event.find_fork("Again").map(|f| f.commands.len()), Some(2));
+    }
+
+    #[test]
+    fn missing_forks_fail_in_strict_mode() {
+        let mut sources = HashMap::new();
+        sources.insert("100".to_owned(), "none/0 0/farmer 1 1 2/fork Nowhere/end".to_owned());
+        let e = parse_event("100", &sources, ParseMode::Strict).err().unwrap();
+        assert_eq!(e.kind, EventParseErrorKind::MissingFork);
+        assert!(parse_event("100", &sources, ParseMode::Lenient).unwrap().forks.is_empty());
+    }
+}
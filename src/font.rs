@@ -0,0 +1,99 @@
+use assets::AssetSource;
+use graphics::{Image, Transformed};
+use graphics::math::Matrix2d;
+use opengl_graphics::{GlGraphics, Texture};
+use std::collections::HashMap;
+use texture::{TextureError, image_from_texture2d, texture_from_image};
+use xnb::{XNB, SpriteFont};
+
+struct Glyph {
+    src: [f64; 4],
+    cropping: (f64, f64),
+    left_bearing: f64,
+    advance: f64,
+}
+
+/// A bitmap font decoded from one of the game's SpriteFont XNBs.
+pub struct Font {
+    texture: Texture,
+    glyphs: HashMap<char, Glyph>,
+    default_char: Option<char>,
+    line_spacing: f64,
+    spacing: f64,
+}
+
+impl Font {
+    pub fn load(assets: &AssetSource, filename: &str) -> Result<Font, TextureError> {
+        let mut f = assets.open(format!("Fonts/{}", filename))?;
+        let xnb = XNB::<SpriteFont>::from_buffer(&mut f)
+            .map_err(|e| TextureError::Decode(format!("Fonts/{}: {:?}", filename, e)))?;
+        let font = xnb.primary;
+
+        let mut glyphs = HashMap::new();
+        for (i, &c) in font.char_map.iter().enumerate() {
+            let missing = || TextureError::Decode(format!("Fonts/{}: no glyph data for {:?}", filename, c));
+            let g = font.glyphs.get(i).ok_or_else(&missing)?;
+            let cropping = font.cropping.get(i).ok_or_else(&missing)?;
+            let kerning = font.kerning.get(i).ok_or_else(&missing)?;
+            glyphs.insert(c, Glyph {
+                src: [g.x as f64, g.y as f64, g.w as f64, g.h as f64],
+                cropping: (cropping.x as f64, cropping.y as f64),
+                left_bearing: kerning.x as f64,
+                advance: (kerning.y + kerning.z) as f64,
+            });
+        }
+
+        Ok(Font {
+            texture: texture_from_image(&image_from_texture2d(font.texture)?),
+            glyphs: glyphs,
+            default_char: font.default_char,
+            line_spacing: font.v_spacing as f64,
+            spacing: font.h_spacing as f64,
+        })
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.default_char.and_then(|d| self.glyphs.get(&d)))
+    }
+
+    pub fn line_height(&self) -> f64 {
+        self.line_spacing
+    }
+
+    /// The width in pixels of the longest line of `text`.
+    pub fn measure(&self, text: &str) -> f64 {
+        text.split('\n').map(|line| {
+            line.chars().filter_map(|c| self.glyph(c)).fold(0., |w, g| {
+                w + g.left_bearing + g.advance + self.spacing
+            })
+        }).fold(0., f64::max)
+    }
+
+    /// Draw `text` with its top-left corner at `(x, y)`, scaled by `scale`.
+    pub fn draw(&self,
+                text: &str,
+                (x, y): (f64, f64),
+                scale: f64,
+                color: [f32; 4],
+                transform: Matrix2d,
+                gl: &mut GlGraphics) {
+        let transform = transform.trans(x, y).zoom(scale);
+        let mut pen = (0., 0.);
+        for c in text.chars() {
+            if c == '\n' {
+                pen = (0., pen.1 + self.line_spacing);
+                continue;
+            }
+            let glyph = match self.glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            pen.0 += glyph.left_bearing;
+            Image::new_color(color)
+                .src_rect(glyph.src)
+                .rect([pen.0 + glyph.cropping.0, pen.1 + glyph.cropping.1, glyph.src[2], glyph.src[3]])
+                .draw(&self.texture, &Default::default(), transform, gl);
+            pen.0 += glyph.advance + self.spacing;
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use assets::AssetSource;
+use image::{Rgba, RgbaImage};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use map::{LayerProps, MapError, SVMap, TileGrid, TileProps, TilesetProps, grid_for_layer};
+use overlay::Overlays;
+use render::image_for_tile_reference;
+use texture::load_image;
+use xnb::tide::Layer;
+
+/// Copy the `src_rect` region of `src` onto `dest` at `dest_rect`, using the
+/// same rectangle conventions as `graphics::Image` (a negative source width
+/// means the source is flipped horizontally). Pixels are alpha-blended.
+fn blit(dest: &mut RgbaImage, src: &RgbaImage, src_rect: [f64; 4], dest_rect: [f64; 4]) {
+    let (src_x, src_y, src_w, src_h) = (src_rect[0] as i32, src_rect[1] as i32,
+                                        src_rect[2] as i32, src_rect[3] as i32);
+    let (dest_x, dest_y) = (dest_rect[0] as i32, dest_rect[1] as i32);
+    let (dest_w, dest_h) = (dest.width() as i32, dest.height() as i32);
+
+    for dy in 0..src_h {
+        for dx in 0..src_w.abs() {
+            let (x, y) = (dest_x + dx, dest_y + dy);
+            if x < 0 || y < 0 || x >= dest_w || y >= dest_h {
+                continue;
+            }
+            let sx = if src_w < 0 { src_x - 1 - dx } else { src_x + dx };
+            let sy = src_y + dy;
+            if sx < 0 || sy < 0 || sx >= src.width() as i32 || sy >= src.height() as i32 {
+                continue;
+            }
+            let over = *src.get_pixel(sx as u32, sy as u32);
+            let under = dest.get_pixel_mut(x as u32, y as u32);
+            *under = blend(*under, over);
+        }
+    }
+}
+
+fn blend(under: Rgba<u8>, over: Rgba<u8>) -> Rgba<u8> {
+    let alpha = over.data[3] as u32;
+    if alpha == 255 {
+        return over;
+    }
+    if alpha == 0 {
+        return under;
+    }
+    let mix = |u: u8, o: u8| ((o as u32 * alpha + u as u32 * (255 - alpha)) / 255) as u8;
+    Rgba([mix(under.data[0], over.data[0]),
+          mix(under.data[1], over.data[1]),
+          mix(under.data[2], over.data[2]),
+          (alpha + under.data[3] as u32 * (255 - alpha) / 255) as u8])
+}
+
+/// Alpha-blend `color` over the `w * h` pixel rectangle at `(x, y)`.
+fn fill(dest: &mut RgbaImage, (x, y, w, h): (u32, u32, u32, u32), color: [f32; 4]) {
+    let over = Rgba([(color[0] * 255.) as u8,
+                     (color[1] * 255.) as u8,
+                     (color[2] * 255.) as u8,
+                     (color[3] * 255.) as u8]);
+    for py in y..(y + h).min(dest.height()) {
+        for px in x..(x + w).min(dest.width()) {
+            let under = dest.get_pixel_mut(px, py);
+            *under = blend(*under, over);
+        }
+    }
+}
+
+/// The tilesheet properties and index of the tile at `(x, y)` on `layer`.
+fn props_at<'a>(map: &'a SVMap,
+                layer: Option<&(&'a Layer<LayerProps, TileProps>, TileGrid<usize>)>,
+                (x, y): (u32, u32),
+                ticks: u32) -> Option<(&'a TilesetProps, u32)> {
+    let &(layer, ref grid) = layer?;
+    let tile = &layer.tiles[*grid.get(x as i32, y as i32)?];
+    let name = tile.get_tilesheet();
+    let sheet = map.tilesheets.iter().find(|s| s.id == name)?;
+    Some((&sheet.properties, tile.get_index(ticks)))
+}
+
+/// Tint each tile within the tile rectangle `(x, y, w, h)` of `output` with
+/// the enabled `overlays`.
+fn draw_overlays(output: &mut RgbaImage,
+                 map: &SVMap,
+                 overlays: Overlays,
+                 (x, y, w, h): (u32, u32, u32, u32),
+                 ticks: u32) {
+    let grid = |id: &str| map.layers.iter().find(|l| l.id == id).map(|l| (l, grid_for_layer(l)));
+    let (back, buildings) = (grid("Back"), grid("Buildings"));
+    // Only tint tiles on the map, even if the rectangle runs past its edge.
+    let size = |layer: &Option<(&Layer<LayerProps, TileProps>, TileGrid<usize>)>| {
+        layer.as_ref().map_or((0, 0), |&(l, _)| l.size)
+    };
+    let (map_w, map_h) = (size(&back).0.max(size(&buildings).0), size(&back).1.max(size(&buildings).1));
+    for ty in y..(y + h).min(map_h) {
+        for tx in x..(x + w).min(map_w) {
+            let tints = overlays.tints(props_at(map, back.as_ref(), (tx, ty), ticks),
+                                       props_at(map, buildings.as_ref(), (tx, ty), ticks));
+            for tint in tints {
+                fill(output, ((tx - x) * 16, (ty - y) * 16, 16, 16), tint);
+            }
+        }
+    }
+}
+
+/// Composite every visible layer of `map` within the tile rectangle
+/// `(x, y, w, h)` into a new image, without any GPU involvement, with the
+/// enabled `overlays` drawn on top.
+pub fn render_map(map: &SVMap,
+                  tilesheets: &HashMap<String, RgbaImage>,
+                  (x, y, w, h): (u32, u32, u32, u32),
+                  overlays: Overlays,
+                  ticks: u32) -> Result<RgbaImage, MapError> {
+    let mut output = RgbaImage::new(w * 16, h * 16);
+    let view = (x as i32 * 16, y as i32 * 16);
+
+    for layer in &map.layers {
+        if !layer.visible || layer.id == "Paths" {
+            continue;
+        }
+        // Tiles are sorted by (y, x), so skip straight to the first row
+        // inside the rectangle and stop after the last one.
+        let first = match layer.tiles.binary_search_by(|t| t.get_pos().1.cmp(&y).then(Ordering::Greater)) {
+            Ok(i) | Err(i) => i,
+        };
+        let rows = layer.tiles[first..].iter().take_while(|t| t.get_pos().1 < y + h);
+        for tile in rows {
+            let (tx, ty) = tile.get_pos();
+            if tx < x || tx >= x + w {
+                continue;
+            }
+            let name = tile.get_tilesheet();
+            let unknown = || MapError::UnknownTilesheet(name.to_owned());
+            let sheet = map.tilesheets.iter().find(|s| s.id == name).ok_or_else(&unknown)?;
+            let texture = tilesheets.get(name).ok_or_else(&unknown)?;
+            let image = image_for_tile_reference(sheet.sheet_size.0,
+                                                 sheet.tile_size,
+                                                 tile.get_index(ticks),
+                                                 0,
+                                                 (tx as i32, ty as i32),
+                                                 (0, 0),
+                                                 view,
+                                                 false);
+            blit(&mut output,
+                 texture,
+                 image.source_rectangle.unwrap(),
+                 image.rectangle.unwrap());
+        }
+    }
+
+    if overlays.any() {
+        draw_overlays(&mut output, map, overlays, (x, y, w, h), ticks);
+    }
+    Ok(output)
+}
+
+/// Load the tilesheet images for `map` and write the rendered tile
+/// rectangle `rect` (or the whole map) to `output` as a PNG.
+pub fn render_to_file(map: &SVMap,
+                      assets: &AssetSource,
+                      rect: Option<(u32, u32, u32, u32)>,
+                      overlays: Overlays,
+                      output: &Path) -> io::Result<()> {
+    let mut tilesheets = HashMap::new();
+    for ts in &map.tilesheets {
+        let image = load_image(assets, &format!("{}.xnb", ts.image_source))?;
+        tilesheets.insert(ts.id.clone(), image);
+    }
+
+    let size = map.layers[0].size;
+    let rect = rect.unwrap_or((0, 0, size.0, size.1));
+    let image = render_map(map, &tilesheets, rect, overlays, 0)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    image.save(output)
+}
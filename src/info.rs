@@ -0,0 +1,87 @@
+use font::Font;
+use graphics::{Context, Transformed, rectangle};
+use map::{MapProps, TileChange};
+use opengl_graphics::GlGraphics;
+
+const PANEL_WIDTH: f64 = 320.;
+const PADDING: f64 = 6.;
+
+fn yes_no(b: bool) -> &'static str {
+    if b { "yes" } else { "no" }
+}
+
+fn tile_change_lines(lines: &mut Vec<String>, title: &str, changes: &[TileChange]) {
+    if changes.is_empty() {
+        return;
+    }
+    lines.push(format!("{}:", title));
+    for change in changes {
+        lines.push(format!("  {} ({}, {}) -> {}", change.layer, change.pos.0, change.pos.1, change.index));
+    }
+}
+
+/// The lines of text describing a map's properties.
+pub fn describe(map_name: &str, props: &MapProps) -> Vec<String> {
+    let mut lines = vec![
+        map_name.to_owned(),
+        format!("Music: {}", props.music.as_ref().map_or("none", |m| &m[..])),
+        format!("Outdoors: {}", yes_no(props.outdoors)),
+        format!("Treat as outdoors: {}", yes_no(props.treat_as_outdoors)),
+        format!("Viewport follows player: {}", yes_no(props.viewport_follow_player)),
+    ];
+    if !props.warps.is_empty() {
+        lines.push("Warps:".to_owned());
+        for warp in &props.warps {
+            lines.push(format!("  ({}, {}) -> {} ({}, {})",
+                               warp.from.0, warp.from.1, warp.map, warp.to.0, warp.to.1));
+        }
+    }
+    if !props.doors.is_empty() {
+        lines.push("Doors:".to_owned());
+        for door in &props.doors {
+            lines.push(format!("  ({}, {}) {} #{}", door.pos.0, door.pos.1, door.sheet, door.index));
+        }
+    }
+    if !props.lights.is_empty() {
+        lines.push("Lights:".to_owned());
+        for light in &props.lights {
+            lines.push(format!("  ({}, {}) type {}", light.pos.0, light.pos.1, light.kind));
+        }
+    }
+    tile_change_lines(&mut lines, "Day tiles", &props.day_tiles);
+    tile_change_lines(&mut lines, "Night tiles", &props.night_tiles);
+    lines
+}
+
+/// An overlay on the right of the window listing the current map's
+/// properties.
+pub struct InfoPanel {
+    pub open: bool,
+}
+
+impl InfoPanel {
+    pub fn new() -> InfoPanel {
+        InfoPanel {
+            open: false,
+        }
+    }
+
+    pub fn draw(&self, font: &Font, map_name: &str, props: &MapProps, c: Context, gl: &mut GlGraphics) {
+        const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+        const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+        let size = c.get_view_size();
+        let x = size[0] - PANEL_WIDTH;
+        rectangle(BACKGROUND, [x, 0., PANEL_WIDTH, size[1]], c.transform, gl);
+
+        let transform = c.transform.trans(x + PADDING, PADDING);
+        let mut y = 0.;
+        for line in describe(map_name, props) {
+            if y + font.line_height() > size[1] - PADDING {
+                break;
+            }
+            font.draw(&line, (0., y), 1., TEXT, transform, gl);
+            y += font.line_height();
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use font::Font;
+use graphics::{Context, Rectangle, Transformed, rectangle};
+use map::{ResolvedTile, SVMap, TileGrid, TileProps, tile_props};
+use opengl_graphics::GlGraphics;
+use xnb::tide::Tile;
+
+const PANEL_WIDTH: f64 = 320.;
+const PADDING: f64 = 6.;
+
+fn tile_lines(lines: &mut Vec<String>, map: &SVMap, layer_id: &str, tile: &Tile<TileProps>, ticks: u32) {
+    match *tile {
+        Tile::Static(ref t) => lines.push(format!("{}: {} #{}", layer_id, t.tilesheet, t.index)),
+        Tile::Animated(ref t) => {
+            let frames: Vec<_> = t.frames.iter().map(|f| format!("{} #{}", f.tilesheet, f.index)).collect();
+            lines.push(format!("{}: every {}ms {}", layer_id, t.frame_interval, frames.join(", ")));
+        }
+    }
+
+    let props = tile_props(tile);
+    if let Some(passable) = props.passable {
+        lines.push(format!("  Passable: {}", if passable { "T" } else { "F" }));
+    }
+    if let Some(ref action) = props.action {
+        lines.push(format!("  Action: {}", action));
+    }
+
+    let name = tile.get_tilesheet();
+    if let Some(sheet) = map.tilesheets.iter().find(|s| s.id == name) {
+        for (k, v) in sheet.properties.tile_properties(tile.get_index(ticks)) {
+            lines.push(format!("  {}: {}", k, v));
+        }
+    }
+}
+
+/// The lines of text describing everything on `map` at `pos`: the tile on
+/// each layer with its properties and those of its tilesheet, and any map
+/// properties that refer to the position.
+pub fn describe_tile(map: &SVMap,
+                     resolved_layers: &[TileGrid<ResolvedTile>],
+                     (x, y): (i32, i32),
+                     ticks: u32) -> Vec<String> {
+    let mut lines = vec![format!("Tile ({}, {})", x, y)];
+    for (layer, resolved) in map.layers.iter().zip(resolved_layers) {
+        match resolved.get(x, y) {
+            Some(r) => tile_lines(&mut lines, map, &layer.id, &layer.tiles[r.tile], ticks),
+            None => lines.push(format!("{}: empty", layer.id)),
+        }
+    }
+
+    let props = &map.properties;
+    if let Some(warp) = props.warp_at((x, y)) {
+        lines.push(format!("Warp to {} ({}, {})", warp.map, warp.to.0, warp.to.1));
+    }
+    for door in props.doors.iter().filter(|d| d.pos == (x, y)) {
+        lines.push(format!("Door: {} #{}", door.sheet, door.index));
+    }
+    for light in props.lights.iter().filter(|l| l.pos == (x, y)) {
+        lines.push(format!("Light: type {}", light.kind));
+    }
+    for &(title, changes) in &[("Day tile", &props.day_tiles), ("Night tile", &props.night_tiles)] {
+        for change in changes.iter().filter(|c| c.pos == (x, y)) {
+            lines.push(format!("{}: {} -> {}", title, change.layer, change.index));
+        }
+    }
+    lines
+}
+
+/// An overlay on the left of the window describing the tile under the
+/// mouse, or the tile that was last clicked.
+pub struct TileInspector {
+    pub open: bool,
+    /// The tile under the mouse.
+    pub hovered: Option<(i32, i32)>,
+    /// The tile that was clicked, shown instead of the hovered one.
+    pub pinned: Option<(i32, i32)>,
+}
+
+impl TileInspector {
+    pub fn new() -> TileInspector {
+        TileInspector {
+            open: false,
+            hovered: None,
+            pinned: None,
+        }
+    }
+
+    /// The tile being inspected.
+    pub fn selected(&self) -> Option<(i32, i32)> {
+        self.pinned.or(self.hovered)
+    }
+
+    /// Pin `pos`, or unpin it if it's already pinned.
+    pub fn click(&mut self, pos: (i32, i32)) {
+        self.pinned = if self.pinned == Some(pos) { None } else { Some(pos) };
+    }
+
+    /// Outline the tile at `pos`, which covers `tile_rect` in the window, and
+    /// list its contents.
+    pub fn draw(&self,
+                font: &Font,
+                map: &SVMap,
+                resolved_layers: &[TileGrid<ResolvedTile>],
+                pos: (i32, i32),
+                tile_rect: [f64; 4],
+                ticks: u32,
+                c: Context,
+                gl: &mut GlGraphics) {
+        const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+        const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+        const OUTLINE: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+        const PINNED_OUTLINE: [f32; 4] = [1.0, 0.5, 0.0, 1.0];
+
+        let outline = if self.pinned.is_some() { PINNED_OUTLINE } else { OUTLINE };
+        Rectangle::new_border(outline, 1.).draw(tile_rect, &c.draw_state, c.transform, gl);
+
+        let size = c.get_view_size();
+        rectangle(BACKGROUND, [0., 0., PANEL_WIDTH, size[1]], c.transform, gl);
+
+        let transform = c.transform.trans(PADDING, PADDING);
+        let mut y = 0.;
+        for line in describe_tile(map, resolved_layers, pos, ticks) {
+            if y + font.line_height() > size[1] - PADDING {
+                break;
+            }
+            font.draw(&line, (0., y), 1., TEXT, transform, gl);
+            y += font.line_height();
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use event::{Command, End, ScriptedEvent};
+use map::{LayerProps, MapProps, SVMap, TileChange, TileProps, TilesetProps};
+use serde_json::Value;
+use xnb::tide::{Layer, Tile, TileSheet};
+
+fn tile_props_to_json(props: &TileProps) -> Value {
+    json!({
+        "passable": props.passable,
+        "action": props.action,
+    })
+}
+
+fn tileset_props_to_json(props: &TilesetProps) -> Value {
+    let flags = |flags: &[(u32, bool)]| -> Value {
+        flags.iter().map(|&(index, value)| json!({"index": index, "value": value})).collect()
+    };
+    let types: Value = props.types.iter().map(|&(index, ref kind)| {
+        json!({"index": index, "value": kind.name()})
+    }).collect();
+    let path_types: Value = props.path_types.iter().map(|&(index, ref path)| {
+        json!({"index": index, "value": path})
+    }).collect();
+    let other: Value = props.other.iter().map(|(&(index, ref name), value)| {
+        json!({"index": index, "name": name, "value": value})
+    }).collect();
+    json!({
+        "passable": flags(&props.passable),
+        "water": flags(&props.water),
+        "types": types,
+        "spawnable": flags(&props.spawnable),
+        "diggable": flags(&props.diggable),
+        "shadow": flags(&props.shadow),
+        "path_types": path_types,
+        "other": other,
+    })
+}
+
+fn tilesheet_to_json(sheet: &TileSheet<TilesetProps>) -> Value {
+    json!({
+        "id": sheet.id,
+        "image_source": sheet.image_source,
+        "sheet_size": [sheet.sheet_size.0, sheet.sheet_size.1],
+        "tile_size": [sheet.tile_size.0, sheet.tile_size.1],
+        "properties": tileset_props_to_json(&sheet.properties),
+    })
+}
+
+fn tile_to_json(tile: &Tile<TileProps>) -> Value {
+    let (x, y) = tile.get_pos();
+    match *tile {
+        Tile::Static(ref t) => json!({
+            "pos": [x, y],
+            "tilesheet": t.tilesheet,
+            "index": t.index,
+            "properties": tile_props_to_json(&t.properties),
+        }),
+        Tile::Animated(ref t) => {
+            let frames: Value = t.frames.iter().map(|f| {
+                json!({"tilesheet": f.tilesheet, "index": f.index})
+            }).collect();
+            json!({
+                "pos": [x, y],
+                "frame_interval": t.frame_interval,
+                "frames": frames,
+                "properties": tile_props_to_json(&t.properties),
+            })
+        }
+    }
+}
+
+fn layer_to_json(layer: &Layer<LayerProps, TileProps>) -> Value {
+    let tiles: Value = layer.tiles.iter().map(tile_to_json).collect();
+    json!({
+        "id": layer.id,
+        "visible": layer.visible,
+        "size": [layer.size.0, layer.size.1],
+        "tiles": tiles,
+    })
+}
+
+fn tile_changes_to_json(changes: &[TileChange]) -> Value {
+    changes.iter().map(|c| {
+        json!({"layer": c.layer, "pos": [c.pos.0, c.pos.1], "index": c.index})
+    }).collect()
+}
+
+fn map_props_to_json(props: &MapProps) -> Value {
+    let warps: Value = props.warps.iter().map(|w| {
+        json!({"from": [w.from.0, w.from.1], "map": w.map, "to": [w.to.0, w.to.1]})
+    }).collect();
+    let doors: Value = props.doors.iter().map(|d| {
+        json!({"pos": [d.pos.0, d.pos.1], "sheet": d.sheet, "index": d.index})
+    }).collect();
+    let lights: Value = props.lights.iter().map(|l| {
+        json!({"pos": [l.pos.0, l.pos.1], "type": l.kind})
+    }).collect();
+    json!({
+        "warps": warps,
+        "doors": doors,
+        "lights": lights,
+        "music": props.music,
+        "outdoors": props.outdoors,
+        "treat_as_outdoors": props.treat_as_outdoors,
+        "viewport_follow_player": props.viewport_follow_player,
+        "day_tiles": tile_changes_to_json(&props.day_tiles),
+        "night_tiles": tile_changes_to_json(&props.night_tiles),
+    })
+}
+
+/// A JSON description of `map`'s properties, tilesheets and layers.
+pub fn map_to_json(map: &SVMap) -> Value {
+    let tilesheets: Value = map.tilesheets.iter().map(tilesheet_to_json).collect();
+    let layers: Value = map.layers.iter().map(layer_to_json).collect();
+    json!({
+        "properties": map_props_to_json(&map.properties),
+        "tilesheets": tilesheets,
+        "layers": layers,
+    })
+}
+
+fn command_to_json(command: &Command) -> Value {
+    match *command {
+        Command::Pause(ms) => json!({"command": "pause", "duration": ms}),
+        Command::Emote(ref actor, emote) => json!({"command": "emote", "actor": actor, "emote": emote}),
+        Command::Move(ref actor, (x, y), dir) =>
+            json!({"command": "move", "actor": actor, "offset": [x, y], "direction": dir}),
+        Command::Speak(ref actor, ref text) => json!({"command": "speak", "actor": actor, "text": text}),
+        Command::GlobalFade => json!({"command": "globalFade"}),
+        Command::Viewport(x, y) => json!({"command": "viewport", "pos": [x, y]}),
+        Command::Warp(ref actor, (x, y)) => json!({"command": "warp", "actor": actor, "pos": [x, y]}),
+        Command::FaceDirection(ref actor, dir) =>
+            json!({"command": "faceDirection", "actor": actor, "direction": dir}),
+        Command::ShowFrame(ref actor, frame) => json!({"command": "showFrame", "actor": actor, "frame": frame}),
+        Command::Speed(ref actor, speed) => json!({"command": "speed", "actor": actor, "speed": speed}),
+        Command::PlaySound(ref sound) => json!({"command": "playSound", "sound": sound}),
+        Command::Shake(ref actor, ms) => json!({"command": "shake", "actor": actor, "duration": ms}),
+        Command::Jump(ref actor) => json!({"command": "jump", "actor": actor}),
+        Command::TextAboveHead(ref actor, ref text) =>
+            json!({"command": "textAboveHead", "actor": actor, "text": text}),
+        Command::AddQuest(id) => json!({"command": "addQuest", "quest": id}),
+        Command::Message(ref text) => json!({"command": "message", "text": text}),
+        Command::Animate(ref actor, flip, looping, interval, ref frames) => json!({
+            "command": "animate",
+            "actor": actor,
+            "flip": flip,
+            "loop": looping,
+            "interval": interval,
+            "frames": frames,
+        }),
+        Command::StopAnimation(ref actor) => json!({"command": "stopAnimation", "actor": actor}),
+        Command::Mail(ref id) => json!({"command": "mail", "mail": id}),
+        Command::Friendship(ref actor, amount) =>
+            json!({"command": "friendship", "actor": actor, "amount": amount}),
+        Command::PlayMusic(ref music) => json!({"command": "playMusic", "music": music}),
+        Command::SpecificTemporarySprite(ref sprite) =>
+            json!({"command": "specificTemporarySprite", "sprite": sprite}),
+        Command::ChangeLocation(ref location) => json!({"command": "changeLocation", "location": location}),
+        Command::ChangeToTemporaryMap(ref map) => json!({"command": "changeToTemporaryMap", "map": map}),
+        Command::Question(ref fork, ref text) => json!({"command": "question", "fork": fork, "text": text}),
+        Command::Fork(ref name) => json!({"command": "fork", "fork": name}),
+        Command::AmbientLight(r, g, b) => json!({"command": "ambientLight", "color": [r, g, b]}),
+        Command::PositionOffset(ref actor, x, y) =>
+            json!({"command": "positionOffset", "actor": actor, "offset": [x, y]}),
+        Command::Unknown(ref args) => json!({"command": null, "args": args}),
+    }
+}
+
+fn end_to_json(end: &End) -> Value {
+    match *end {
+        End::WarpOut => json!({"end": "warpOut"}),
+        End::Dialogue(ref actor, ref text) => json!({"end": "dialogue", "actor": actor, "text": text}),
+        End::DialogueWarpOut(ref actor, ref text) =>
+            json!({"end": "dialogueWarpOut", "actor": actor, "text": text}),
+        End::Position((x, y)) => json!({"end": "position", "pos": [x, y]}),
+        End::NewDay => json!({"end": "newDay"}),
+        End::Invisible(ref actor) => json!({"end": "invisible", "actor": actor}),
+        End::InvisibleWarpOut(ref actor) => json!({"end": "invisibleWarpOut", "actor": actor}),
+        End::End => json!({"end": "end"}),
+        End::Other(ref args) => json!({"end": null, "args": args}),
+    }
+}
+
+/// A JSON description of a parsed event, including its forks.
+pub fn event_to_json(event: &ScriptedEvent) -> Value {
+    let characters: Value = event.characters.iter().map(|c| {
+        json!({"name": c.name, "pos": [c.pos.0, c.pos.1], "direction": c.dir})
+    }).collect();
+    let commands: Value = event.commands.iter().map(command_to_json).collect();
+    let forks: Value = event.forks.iter().map(event_to_json).collect();
+    json!({
+        "id": event.id,
+        "music": event.music,
+        "viewport": [event.viewport.0, event.viewport.1],
+        "skippable": event.skippable,
+        "characters": characters,
+        "commands": commands,
+        "end": end_to_json(&event.end),
+        "forks": forks,
+    })
+}
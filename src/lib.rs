@@ -0,0 +1,54 @@
+extern crate base64;
+extern crate graphics;
+extern crate image;
+extern crate opengl_graphics;
+extern crate piston;
+#[macro_use]
+extern crate serde_json;
+extern crate squish;
+extern crate xml;
+extern crate xnb;
+
+pub mod app;
+pub mod assets;
+pub mod browser;
+pub mod character;
+pub mod dialogue;
+pub mod event;
+pub mod font;
+pub mod headless;
+pub mod info;
+pub mod inspector;
+pub mod json;
+pub mod location;
+pub mod map;
+pub mod overlay;
+pub mod render;
+pub mod runner;
+pub mod tbin;
+pub mod texture;
+pub mod tmx;
+pub mod trigger;
+pub mod validate;
+
+pub use app::App;
+pub use assets::{AssetError, AssetSource};
+pub use browser::EventBrowser;
+pub use character::{Actor, Character, Player, PlayerDir, characters_for_event};
+pub use event::{Command, End, EventLoadError, EventParseError, EventParseErrorKind, ParseMode};
+pub use event::{ScriptedCharacter, ScriptedEvent, load_event, load_event_sources, parse_event, parse_script};
+pub use event::{event_keys, fork_targets, parse_fork_script};
+pub use font::Font;
+pub use info::InfoPanel;
+pub use inspector::TileInspector;
+pub use location::{Location, LocationCache, LocationError};
+pub use map::{LayerProps, MapError, MapProps, ResolvedTile, SVMap, TileGrid, TileProps, TileType, TilesetProps};
+pub use map::{Door, Light, TileChange, Warp};
+pub use map::{grid_for_layer, load_map, load_tilesheets, map_file, resolve_layers, tile_props};
+pub use overlay::Overlays;
+pub use runner::EventRunner;
+pub use texture::{TextureError, TextureTileInfo, load_image, load_texture};
+pub use trigger::{GameState, Trigger};
+
+/// The zoom the viewer starts at, in window pixels per map pixel.
+pub const SCALE: f64 = 1.5;
@@ -0,0 +1,95 @@
+use assets::AssetSource;
+use map::{MapError, SVMap, load_map, load_tilesheets};
+use opengl_graphics::Texture;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use texture::TextureError;
+
+/// Why a location couldn't be loaded.
+#[derive(Debug)]
+pub enum LocationError {
+    Map(MapError),
+    Texture(TextureError),
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LocationError::Map(ref e) => write!(f, "{}", e),
+            LocationError::Texture(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LocationError {
+    fn description(&self) -> &str {
+        match *self {
+            LocationError::Map(ref e) => e.description(),
+            LocationError::Texture(ref e) => e.description(),
+        }
+    }
+}
+
+impl From<MapError> for LocationError {
+    fn from(e: MapError) -> LocationError {
+        LocationError::Map(e)
+    }
+}
+
+impl From<TextureError> for LocationError {
+    fn from(e: TextureError) -> LocationError {
+        LocationError::Texture(e)
+    }
+}
+
+/// A map along with the tilesheet textures needed to draw it.
+pub struct Location {
+    pub name: String,
+    pub map: SVMap,
+    pub tilesheets: HashMap<String, Texture>,
+}
+
+impl Location {
+    pub fn load(assets: &AssetSource, name: &str) -> Result<Location, LocationError> {
+        let map = load_map(assets, name)?;
+        let tilesheets = load_tilesheets(&map, assets)?;
+        Ok(Location {
+            name: name.to_owned(),
+            map: map,
+            tilesheets: tilesheets,
+        })
+    }
+}
+
+/// The most recently visited locations, so walking back and forth between
+/// maps doesn't reload them each time.
+pub struct LocationCache {
+    locations: VecDeque<Location>,
+    capacity: usize,
+}
+
+impl LocationCache {
+    pub fn new(capacity: usize) -> LocationCache {
+        LocationCache {
+            locations: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// The location for the map file `name`, loading it if it isn't cached.
+    pub fn get(&mut self, assets: &AssetSource, name: &str) -> Result<&Location, LocationError> {
+        match self.locations.iter().position(|l| l.name == name) {
+            Some(i) => {
+                let location = self.locations.remove(i).expect("cached location");
+                self.locations.push_front(location);
+            }
+            None => {
+                let location = Location::load(assets, name)?;
+                self.locations.push_front(location);
+                self.locations.truncate(self.capacity);
+            }
+        }
+        Ok(&self.locations[0])
+    }
+}
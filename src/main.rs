@@ -1,784 +1,450 @@
-extern crate graphics;
 extern crate piston_window;
-extern crate image;
 extern crate opengl_graphics;
 extern crate piston;
-extern crate squish;
-extern crate xnb;
+extern crate sv_map_viewer;
 
-use graphics::Image;
-use image::RgbaImage;
-use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings, Filter, ImageSize};
+mod cli;
+
+use opengl_graphics::OpenGL;
 use piston_window::{PistonWindow, WindowSettings, OpenGL as PistonOpenGL};
 use piston::input::*;
+use cli::{Command, DumpFormat};
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fmt::Display;
 use std::path::Path;
-use squish::{decompress_image, CompressType};
-use xnb::{XNB, SurfaceFormat, Texture2d, Dictionary};
-use xnb::tide::{TileSheet, Layer, Map, PropertyValue, PropertyParse};
-
-const SCALE: f64 = 1.5;
-
-type SVMap = Map<MapProps, TilesetProps, LayerProps, TileProps>;
-
-struct LayerProps;
-impl PropertyParse for LayerProps {
-    fn parse(_props: Vec<(String, PropertyValue)>) -> Self {
-        LayerProps
+use std::process;
+use sv_map_viewer::{Actor, App, AssetSource, Character, EventBrowser, EventLoadError, EventRunner, Font, ParseMode};
+use sv_map_viewer::{Player, PlayerDir, ScriptedEvent, parse_event};
+use sv_map_viewer::{GameState, characters_for_event, headless, load_event, load_event_sources};
+use sv_map_viewer::{InfoPanel, Location, LocationCache, Overlays, TileInspector, Warp, info, json, tmx, trigger, validate};
+use sv_map_viewer::{load_map, map_file, resolve_layers, tile_props};
+
+/// Unwrap `result`, or report the error and exit.
+fn or_exit<T, E: Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
     }
 }
 
-struct MapProps;
-impl PropertyParse for MapProps {
-    fn parse(_props: Vec<(String, PropertyValue)>) -> Self {
-        MapProps
-    }
+fn render_main(assets: &AssetSource,
+               map_name: &str,
+               output: &str,
+               rect: Option<(u32, u32, u32, u32)>,
+               overlays: Overlays) {
+    let map = or_exit(load_map(assets, map_name));
+    or_exit(headless::render_to_file(&map, assets, rect, overlays, Path::new(output)));
+    println!("wrote {}", output);
 }
 
-struct TileProps {
-    passable: Option<bool>,
-}
-impl PropertyParse for TileProps {
-    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
-        let mut passable = None;
-        for (k, v) in props {
-            match (k.as_ref(), v) {
-                ("Passable", PropertyValue::String(b)) => {
-                    println!("passable is {:?}", b);
-                    passable = Some(b == "T")
-                }
-                ("Passable", v) => println!("passable: {:?}", v),
-                (k, v) => println!("tile: {} = {:?}", k, v),
+fn dump_main(assets: &AssetSource, map_name: &str, format: DumpFormat, output: &str, event_id: Option<&str>) {
+    if let Some(id) = event_id {
+        match or_exit(load_event(assets, map_name, id, ParseMode::Lenient)) {
+            Some(event) => println!("{:#}", json::event_to_json(&event)),
+            None => {
+                println!("no event {} for {}", id, map_name);
+                process::exit(1);
             }
         }
+        return;
+    }
 
-        Self {
-            passable: passable
+    let map = or_exit(load_map(assets, map_name));
+    match format {
+        DumpFormat::Tmx => {
+            let path = or_exit(tmx::export_tmx(&map, assets, map_name, Path::new(output)));
+            println!("wrote {}", path.display());
+            return;
         }
+        DumpFormat::Json => {
+            println!("{:#}", json::map_to_json(&map));
+            return;
+        }
+        DumpFormat::Text => {}
     }
-}
 
-enum TileType {
-    Grass,
-    Stone,
-    Dirt,
-    Wood,
-}
-
-struct TilesetProps {
-    passable: Vec<(u32, bool)>,
-    water: Vec<(u32, bool)>,
-    types: Vec<(u32, TileType)>,
-}
-
-impl TilesetProps {
-    fn tile_is_passable(&self, idx: u32) -> Option<bool> {
-        self.passable.iter().find(|&&(i, _v)| i == idx).map(|&(_, v)| v)
+    for line in info::describe(map_name, &map.properties) {
+        println!("{}", line);
+    }
+    for sheet in &map.tilesheets {
+        println!("  tilesheet {}: {} ({}x{} tiles of {}x{})",
+                 sheet.id,
+                 sheet.image_source,
+                 sheet.sheet_size.0,
+                 sheet.sheet_size.1,
+                 sheet.tile_size.0,
+                 sheet.tile_size.1);
+    }
+    for layer in &map.layers {
+        println!("  layer {}: {}x{}, {} tiles{}",
+                 layer.id,
+                 layer.size.0,
+                 layer.size.1,
+                 layer.tiles.len(),
+                 if layer.visible { "" } else { " (hidden)" });
     }
 }
 
-impl PropertyParse for TilesetProps {
-    fn parse(_props: Vec<(String, PropertyValue)>) -> Self {
-        let mut passable = vec![];
-        let mut water = vec![];
-        let mut types = vec![];
-
-        for (k, v) in _props {
-            let mut parts = k.split('@');
-            let _ = parts.next();
-            let category = parts.next().unwrap();
-            if category != "TileIndex" {
-                continue;
-            }
-            let idx = parts.next().unwrap().parse().unwrap();
-            let name = parts.next().unwrap();
-            match (name, v) {
-                ("Spawnable", _) => (),
-                ("Diggable", _) => (),
-                ("PathType", _) => (),
-                ("Shadow", _) => (),
-                ("Passable", PropertyValue::String(ref s)) => passable.push((idx, s == "T")),
-                ("Water", PropertyValue::String(ref s)) => water.push((idx, s == "T")),
-                ("Type", PropertyValue::String(ref s)) => types.push((idx, match s.as_ref() {
-                    "Dirt" => TileType::Dirt,
-                    "Stone" => TileType::Stone,
-                    "Grass" => TileType::Grass,
-                    "Wood" => TileType::Wood,
-                    s => panic!("unexpected tile type: {}", s),
-                })),
-                (s, v) => panic!("unexpected property {}: {:?}", s, v),
-            }
+fn tiles_main(assets: &AssetSource, map_name: &str, layer_name: Option<&str>) {
+    let map = or_exit(load_map(assets, map_name));
+    if let Some(name) = layer_name {
+        if !map.layers.iter().any(|l| l.id == name) {
+            println!("no layer named {} in {}", name, map_name);
+            process::exit(1);
+        }
+    }
+    for layer in &map.layers {
+        if layer_name.map_or(false, |name| name != layer.id) {
+            continue;
         }
-        TilesetProps {
-            passable: passable,
-            water: water,
-            types: types,
+        for tile in &layer.tiles {
+            let (x, y) = tile.get_pos();
+            println!("{}\t{}\t{}\t{}\t{}", layer.id, x, y, tile.get_tilesheet(), tile.get_index(0));
         }
     }
 }
 
-struct ResolvedTile<'a> {
-    texture: &'a Texture,
-    tilesheet: &'a TileSheet<TilesetProps>,
-}
-
-struct Character {
-    _name: String,
-    texture: TextureTileInfo,
-    _index: u32,
-    x: i32,
-    y: i32,
-    offset_x: f64,
-    offset_y: f64,
-    dir: PlayerDir,
-}
-
-pub struct App {
-    gl: GlGraphics,
-    view_x: i32,
-    view_y: i32,
-    view_w: u32,
-    view_h: u32,
-    ticks: u32,
-    d_pressed: bool,
-    a_pressed: bool,
-    w_pressed: bool,
-    s_pressed: bool,
-    update_last_move: bool,
-}
-
-struct Tile<'a> {
-    sheet: &'a TileSheet<TilesetProps>,
-    index: u32,
+fn validate_main(assets: &AssetSource) {
+    let reports = or_exit(validate::validate_events(assets));
+    let mut total = 0;
+    for report in &reports {
+        println!("{}: {} events, {} issues", report.file, report.events, report.issues.len());
+        for issue in &report.issues {
+            println!("  {}", issue);
+        }
+        total += report.issues.len();
+    }
+    println!("{} issues in {} files", total, reports.len());
+    if total > 0 {
+        process::exit(1);
+    }
 }
 
-fn image_for_tile(tile: &Tile, pos: (i32, i32), view: (i32, i32)) -> Image {
-    let num_h_tiles = tile.sheet.sheet_size.0;
-    let tile_w = tile.sheet.tile_size.0;
-    let tile_h = tile.sheet.tile_size.1;
-    image_for_tile_reference(num_h_tiles,
-                             (tile_w, tile_h),
-                             tile.index,
-                             0,
-                             pos,
-                             (0, 0),
-                             view,
-                             false)
-}
+fn events_main(assets: &AssetSource, map_name: &str, settings: &[String]) {
+    let mut state = GameState::default();
+    for setting in settings {
+        or_exit(state.set(setting));
+    }
 
-#[derive(Copy, Clone, PartialEq)]
-enum PlayerDir {
-    Down = 0,
-    Right = 1,
-    Up = 2,
-    Left = 3,
+    let sources = match load_event_sources(assets, map_name) {
+        Ok(sources) => sources,
+        Err(EventLoadError::Missing(_)) => HashMap::new(),
+        Err(e) => or_exit(Err(e)),
+    };
+    for id in trigger::firing_events(&sources, &state) {
+        println!("{}", id);
+    }
 }
 
-fn image_for_texture(texture: &TextureTileInfo,
-                     pos: (i32, i32),
-                     view: (i32, i32),
-                     offset: (i32, i32),
-                     anim: Option<(u32, u32)>,
-                     dir: PlayerDir) -> Image {
-    let num_h_tiles = texture.0.get_width() / (texture.2).0;
-    let offset = ((texture.3).0 + offset.0, (texture.3).1 + offset.1);
-    let base = texture.4[dir as usize].unwrap_or(0);
-    let flip = dir == PlayerDir::Left && texture.4[PlayerDir::Left as usize] == texture.4[PlayerDir::Right as usize];
-    let anim_idx = anim.map_or(0, |a| a.0 / 150 % a.1);
-    image_for_tile_reference(num_h_tiles,
-                             texture.2.clone(),
-                             texture.1 + anim_idx,
-                             base,
-                             pos,
-                             offset,
-                             view,
-                             flip)
+/// Load an event's characters and put the farmer at its starting position,
+/// returning the characters and a runner ready to play the event.
+fn start_event(event: &ScriptedEvent,
+               assets: &AssetSource,
+               player: &mut Player) -> (Vec<Character>, EventRunner) {
+    let mut runner = EventRunner::new();
+    runner.set_return_position(player.position());
+    if let Some(farmer) = event.characters.iter().find(|c| c.name == "farmer") {
+        player.set_position(farmer.pos, (0., 0.));
+        player.dir = PlayerDir::from_event_dir(farmer.dir).unwrap_or(PlayerDir::Down);
+    }
+    let (characters, errors) = characters_for_event(event, assets);
+    for e in errors {
+        println!("{}", e);
+    }
+    (characters, runner)
 }
 
-fn image_for_tile_reference(num_h_tiles: u32,
-                            (tile_w, tile_h): (u32, u32),
-                            index: u32,
-                            index_y_offset: u32,
-                            (x, y): (i32, i32),
-                            (off_x, off_y): (i32, i32),
-                            (view_x, view_y): (i32, i32),
-                            flip_h: bool) -> Image {
-    let src_x = index % num_h_tiles * tile_w;
-    let src_y = (index / num_h_tiles + index_y_offset) * tile_h;
-    let src_rect = if flip_h {
-        [src_x as i32 + tile_w as i32,
-         src_y as i32,
-         -(tile_w as i32),
-         tile_h as i32]
-    } else {
-        [src_x as i32, src_y as i32, tile_w as i32, tile_h as i32]
+fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
     };
-    Image::new()
-        .src_rect([src_rect[0] as f64, src_rect[1] as f64, src_rect[2] as f64, src_rect[3] as f64])
-        .rect([(x as i32 * 16) as f64 + off_x as f64 - view_x as f64,
-               (y as i32 * 16) as f64 + off_y as f64 - view_y as f64,
-               tile_w as f64,
-               tile_h as f64])
+    let mut assets = AssetSource::from_config(options.assets.as_ref().map(|s| &s[..]));
+    assets.prefer_png = options.prefer_png;
+
+    match options.command {
+        Command::View { map, event, at } => view_main(&assets, map, event, at),
+        Command::Render { map, output, rect, overlays } => render_main(&assets, &map, &output, rect, overlays),
+        Command::Dump { map, format, output, event } => {
+            dump_main(&assets, &map, format, &output, event.as_ref().map(|s| &s[..]))
+        }
+        Command::Events { map, settings } => events_main(&assets, &map, &settings),
+        Command::Validate => validate_main(&assets),
+        Command::Tiles { map, layer } => tiles_main(&assets, &map, layer.as_ref().map(|s| &s[..])),
+        Command::Help => print!("{}", cli::USAGE),
+    }
 }
 
-type TextureTileInfo = (Texture, u32, (u32, u32), (i32, i32), [Option<u32>; 4]);
-
-struct Player {
-    base: TextureTileInfo,
-    bottom: TextureTileInfo,
-    arms: TextureTileInfo,
-    pants: TextureTileInfo,
-    hairstyle: TextureTileInfo,
-    hat: Option<TextureTileInfo>,
-    shirt: TextureTileInfo,
-    accessory: TextureTileInfo,
-    x: i32,
-    y: i32,
-    offset_x: f64,
-    offset_y: f64,
-    last_move_start: Option<u32>,
-    dir: PlayerDir,
+/// How many maps to keep loaded after walking out of them.
+const CACHED_LOCATIONS: usize = 4;
+
+/// How much one notch of the mouse wheel or one press of +/- zooms by.
+const ZOOM_STEP: f64 = 1.25;
+
+/// How far the mouse has to move with the button held before a click
+/// becomes a drag, in window pixels.
+const DRAG_THRESHOLD: f64 = 4.;
+
+/// The state of the viewer that carries over from one location to the next.
+struct Viewer<'a> {
+    assets: &'a AssetSource,
+    window: PistonWindow,
+    app: App,
+    font: Font,
+    player: Player,
+    info_panel: InfoPanel,
+    inspector: TileInspector,
+    /// The last known position of the mouse in the window.
+    cursor: (f64, f64),
+    /// Where the left mouse button was pressed, while it's held.
+    drag_start: Option<(f64, f64)>,
+    /// Whether the mouse has moved far enough since `drag_start` to pan.
+    dragged: bool,
+    show_frame_times: bool,
 }
 
-impl Player {
-    fn adjusted_pos(&self, delta_x: f64, delta_y: f64) -> (i32, i32) {
-        let x = self.x + (if delta_x < 0. && self.offset_x + delta_x < -8. {
-            -1
-        } else if delta_x > 0. && self.offset_x + delta_x > 8. {
-           1
-        } else {
-            0
-        });
-
-        let y = self.y + (if delta_y < 0. && self.offset_y + delta_y < -8. {
-            -1
-        } else if delta_y > 0. && self.offset_y + delta_y > 8. {
-           1
-        } else {
-            0
-        });
-
-        (x, y)
+/// The tile in front of `player`.
+fn facing_tile(player: &Player) -> (i32, i32) {
+    match player.dir {
+        PlayerDir::Up => (player.x, player.y - 1),
+        PlayerDir::Down => (player.x, player.y + 1),
+        PlayerDir::Left => (player.x - 1, player.y),
+        PlayerDir::Right => (player.x + 1, player.y),
     }
+}
 
-    fn move_horiz(&mut self, delta: f64, clamp_to_current_pos: bool) {
-        self.offset_x += delta;
-        if delta < 0. && self.offset_x < -8. {
-            if clamp_to_current_pos {
-                self.offset_x = -7.99;
-            } else {
-                self.offset_x = 8.;
-                self.x -= 1;
+impl<'a> Viewer<'a> {
+    /// Show `location` until the window is closed, returning the warp the
+    /// farmer took if they left through one.
+    fn run_location(&mut self, location: &Location, event_id: Option<String>) -> Option<Warp> {
+        let assets = self.assets;
+        let map = &location.map;
+        let map_name = &location.name;
+        self.inspector.pinned = None;
+
+        let mut event = event_id.and_then(|id| {
+            match load_event(assets, map_name, &id, ParseMode::Lenient) {
+                Ok(event) => event,
+                Err(e) => {
+                    println!("{}", e);
+                    None
+                }
             }
-        } else if delta > 0. && self.offset_x > 8. {
-            if clamp_to_current_pos {
-                self.offset_x = 7.99;
-            } else {
-                self.offset_x = -8.;
-                self.x += 1;
+        });
+        // Not every map has events.
+        let sources = match load_event_sources(assets, map_name) {
+            Ok(sources) => sources,
+            Err(EventLoadError::Missing(_)) => HashMap::new(),
+            Err(e) => {
+                println!("{}", e);
+                HashMap::new()
             }
-        }
-    }
+        };
+        let mut browser = EventBrowser::new(&sources);
 
-    fn move_vert(&mut self, delta: f64, clamp_to_current_pos: bool) {
-        self.offset_y += delta;
-        if delta < 0. && self.offset_y < -8. {
-            if clamp_to_current_pos {
-                self.offset_y = -7.99;
-            } else {
-                self.offset_y = 8.;
-                self.y -= 1;
-            }
-        } else if delta > 0. && self.offset_y > 8. {
-            if clamp_to_current_pos {
-                self.offset_y = 7.99;
-            } else {
-                self.offset_y = -8.;
-                self.y += 1;
+        let resolved_layers = match resolve_layers(map, &location.tilesheets) {
+            Ok(layers) => layers,
+            Err(e) => {
+                println!("{}: {}", map_name, e);
+                return None;
             }
-        }
-    }
-}
-
-
-impl App {
-    fn render(&mut self,
-              args: &RenderArgs,
-              player: &Player,
-              characters: &[Character],
-              layers: &[Layer<LayerProps, TileProps>],
-              resolved_layers: &[Vec<ResolvedTile>]) {
-        use graphics::*;
-
-        const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
-
-        let view_x = self.view_x;
-        let view_y = self.view_y;
-
-        self.view_w = args.viewport().window_size[0];
-        self.view_h = args.viewport().window_size[1];
-        let view_w = args.viewport().window_size[0] as i32 / 16 + view_x / 16;
-        let view_h = args.viewport().window_size[1] as i32 / 16 + view_y / 16;
+        };
+        let buildings = map.layers.iter().position(|l| l.id == "Buildings");
 
-        let ticks = self.ticks;
+        let (mut characters, mut runner) = match event {
+            Some(ref ev) => start_event(ev, assets, &mut self.player),
+            None => (vec![], EventRunner::new()),
+        };
 
-        fn draw_character(character: &Character,
-                          transform: [[f64; 3]; 2],
-                          gl: &mut GlGraphics,
-                          (view_x, view_y): (i32, i32),
-                          (_view_w, _view_h): (i32, i32)) {
-            if character.x < 0 || character.y < 0 {
-                return;
-            }
-            let image = image_for_texture(&character.texture,
-                                          (character.x, character.y),
-                                          (view_x, view_y),
-                                          (character.offset_x as i32, character.offset_y as i32),
-                                          None,
-                                          character.dir);
-            image.draw(&character.texture.0, &Default::default(), transform, gl);
+        let tile_size = map.tilesheets[0].tile_size;
+        if let Some(ref event) = event {
+            self.app.view_x = event.viewport.0 * tile_size.0 as i32;
+            self.app.view_y = event.viewport.1 * tile_size.1 as i32;
         }
 
-        fn draw_layer(layer: &Layer<LayerProps, TileProps>,
-                      resolved_tiles: &[ResolvedTile],
-                      transform: [[f64; 3]; 2],
-                      gl: &mut GlGraphics,
-                      ticks: u32,
-                      (view_x, view_y): (i32, i32),
-                      (view_w, view_h): (i32, i32),
-                      player: Option<&Player>) {
-            if !layer.visible  || layer.id == "Paths" {
-                return;
-            }
-            let mut last_pos = None;
-            for (base_tile, resolved) in layer.tiles.iter().zip(resolved_tiles) {
-                let tile = Tile {
-                    sheet: resolved.tilesheet,
-                    index: base_tile.get_index(ticks),
-                };
-                let (x, y) = base_tile.get_pos();
-                let (x, y) = (x as i32, y as i32);
-
-                if let Some(player) = player {
-                    if y == player.y + 1 &&
-                        x >= player.x &&
-                        last_pos.map_or(false, |(tx, _)| tx < player.x)
-                    {
-                        draw_player(player, gl, transform.clone(), (view_x, view_y), ticks);
+        // Only stepping onto a warp triggers it, not arriving on one.
+        let mut last_tile = (self.player.x, self.player.y);
+
+        while let Some(e) = self.window.next() {
+            let app = &mut self.app;
+            let player = &mut self.player;
+            let font = &self.font;
+            let info_panel = &mut self.info_panel;
+            let inspector = &mut self.inspector;
+            let cursor = &mut self.cursor;
+            let drag_start = &mut self.drag_start;
+            let dragged = &mut self.dragged;
+            let show_frame_times = &mut self.show_frame_times;
+
+            if let Some(Button::Keyboard(k)) = e.press_args() {
+                match k {
+                    k if runner.wants_input() => runner.key_pressed(k),
+                    Key::F3 => *show_frame_times = !*show_frame_times,
+                    Key::F5 => app.overlays.passable = !app.overlays.passable,
+                    Key::F6 => app.overlays.water = !app.overlays.water,
+                    Key::F7 => app.overlays.types = !app.overlays.types,
+                    Key::F8 => app.overlays.spawnable = !app.overlays.spawnable,
+                    Key::F9 => app.overlays.diggable = !app.overlays.diggable,
+                    Key::F10 => app.overlays.shadow = !app.overlays.shadow,
+                    Key::F11 => app.overlays.path_types = !app.overlays.path_types,
+                    Key::F12 => app.overlays.other = !app.overlays.other,
+                    Key::E => browser.open = !browser.open,
+                    Key::I => info_panel.open = !info_panel.open,
+                    Key::T => inspector.open = !inspector.open,
+                    k if browser.open => {
+                        let chosen = browser.key_pressed(k)
+                            .map(|key| parse_event(&key, &sources, ParseMode::Lenient));
+                        match chosen {
+                            Some(Ok(ev)) => {
+                                let (c, r) = start_event(&ev, assets, player);
+                                characters = c;
+                                runner = r;
+                                app.view_x = ev.viewport.0 * tile_size.0 as i32;
+                                app.view_y = ev.viewport.1 * tile_size.1 as i32;
+                                event = Some(ev);
+                            }
+                            Some(Err(e)) => println!("{}", e),
+                            None => {}
+                        }
+                    }
+                    Key::X if !app.scripted => {
+                        let (x, y) = facing_tile(player);
+                        let warp = buildings.and_then(|b| {
+                            let resolved = resolved_layers[b].get(x, y)?;
+                            tile_props(&map.layers[b].tiles[resolved.tile]).action_warp((x, y))
+                        });
+                        if warp.is_some() {
+                            return warp;
+                        }
                     }
+                    Key::Equals | Key::Plus | Key::NumPadPlus => {
+                        let center = app.window_center();
+                        app.zoom_by(ZOOM_STEP, center);
+                    }
+                    Key::Minus | Key::NumPadMinus => {
+                        let center = app.window_center();
+                        app.zoom_by(1. / ZOOM_STEP, center);
+                    }
+                    Key::D0 => app.fit_to_window(map.layers[0].size),
+                    Key::Left => app.pan((-16., 0.)),
+                    Key::Right => app.pan((16., 0.)),
+                    Key::Up => app.pan((0., -16.)),
+                    Key::Down => app.pan((0., 16.)),
+                    k => app.key_pressed(k),
                 }
-                last_pos = Some((x, y));
+            }
 
-                if x < view_x / 16 || x > view_w || y < view_y / 16 || y > view_h {
-                    continue;
+            if let Some([x, y]) = e.mouse_cursor_args() {
+                if let Some((start_x, start_y)) = *drag_start {
+                    if (x - start_x).abs().max((y - start_y).abs()) > DRAG_THRESHOLD {
+                        *dragged = true;
+                    }
+                    if *dragged {
+                        app.pan(((cursor.0 - x) / app.zoom, (cursor.1 - y) / app.zoom));
+                    }
                 }
-                let image = image_for_tile(&tile, (x, y), (view_x, view_y));
-                image.draw(resolved.texture, &Default::default(), transform, gl);
+                *cursor = (x, y);
             }
-        }
 
-        fn draw_player(
-            player: &Player,
-            gl: &mut GlGraphics,
-            transform: [[f64; 3]; 2],
-            view: (i32, i32),
-            ticks: u32,
-        ) {
-            let pos = (player.x as i32, player.y as i32);
-            let offset = (player.offset_x as i32, player.offset_y as i32);
-
-            let player_ticks = match player.last_move_start {
-                Some(start) => ticks - start,
-                None => 0,
-            };
-
-            let three_frame = Some((player_ticks, 3));
-
-            // Body
-            let image = image_for_texture(&player.base, pos, view, offset, three_frame, player.dir);
-            image.draw(&player.base.0, &Default::default(), transform, gl);
-            let image = image_for_texture(&player.bottom, pos, view, offset, three_frame, player.dir);
-            image.draw(&player.bottom.0, &Default::default(), transform, gl);
-
-            // Hair
-            let image = image_for_texture(&player.hairstyle, pos, view, offset, None, player.dir);
-            image.draw(&player.hairstyle.0, &Default::default(), transform, gl);
-
-            // Hat
-            if let Some(ref hat) = player.hat {
-                let image = image_for_texture(hat, pos, view, offset, None, player.dir);
-                image.draw(&hat.0, &Default::default(), transform, gl);
+            if let Some([_, scroll]) = e.mouse_scroll_args() {
+                if scroll != 0. {
+                    app.zoom_by(ZOOM_STEP.powf(scroll), *cursor);
+                }
             }
 
-            // Arms
-            let image = image_for_texture(&player.arms, pos, view, offset, three_frame, player.dir);
-            image.draw(&player.arms.0, &Default::default(), transform, gl);
-
-            // Pants
-            let image = image_for_texture(&player.pants, pos, view, offset, three_frame, player.dir);
-            image.draw(&player.pants.0, &Default::default(), transform, gl);
-
-            // Shirt
-            let image = image_for_texture(&player.shirt, pos, view, offset, None, player.dir);
-            image.draw(&player.shirt.0, &Default::default(), transform, gl);
-
-            // Facial accessory
-            if player.dir != PlayerDir::Up {
-                let image = image_for_texture(&player.accessory, pos, view, offset, None, player.dir);
-                image.draw(&player.accessory.0, &Default::default(), transform, gl);
+            if let Some(Button::Mouse(MouseButton::Left)) = e.press_args() {
+                *drag_start = Some(*cursor);
+                *dragged = false;
             }
-        }
-
-        self.gl.draw(args.viewport(), |c, gl| {
-            // Clear the screen.
-            clear(BLACK, gl);
 
-            let transform = c.transform.zoom(SCALE);
-
-            for (i, (layer, resolved)) in layers.iter().zip(resolved_layers).enumerate() {
-                if i == layers.len() - 1 {
-                    break;
+            // A click that didn't turn into a drag picks a tile to inspect.
+            if let Some(Button::Mouse(MouseButton::Left)) = e.release_args() {
+                if drag_start.take().is_some() && !*dragged && inspector.open && !browser.open {
+                    inspector.click(app.tile_at(*cursor));
                 }
-                let player = if i == 1 { Some(player) } else { None };
-                draw_layer(layer, resolved, transform, gl, ticks,
-                           (view_x, view_y), (view_w, view_h), player);
             }
 
-            for character in characters {
-                draw_character(character, transform, gl,
-                               (view_x, view_y), (view_w, view_h));
+            if let Some(Button::Keyboard(k)) = e.release_args() {
+                app.key_released(k);
             }
 
-            draw_layer(layers.last().unwrap(),
-                       resolved_layers.last().unwrap(),
-                       transform, gl, ticks,
-                       (view_x, view_y), (view_w, view_h), None);
-        });
-    }
-
-    fn key_released(&mut self, key: Key) {
-        match key {
-            Key::A => self.a_pressed = false,
-            Key::D => self.d_pressed = false,
-            Key::W => self.w_pressed = false,
-            Key::S => self.s_pressed = false,
-            _ => {}
-        }
-
-        self.update_last_move = true;
-    }
-
-    fn key_pressed(&mut self, key: Key) {
-        if key == Key::W && !self.w_pressed ||
-            key == Key::S && !self.s_pressed ||
-            key == Key::A && !self.a_pressed ||
-            key == Key::D && !self.d_pressed {
-            self.update_last_move = true
-        }
-
-        match key {
-            Key::A => self.a_pressed = true,
-            Key::D => self.d_pressed = true,
-            Key::S => self.s_pressed = true,
-            Key::W => self.w_pressed = true,
-            _ => {}
-        }
-    }
-
-    fn update(&mut self, args: &UpdateArgs, player: &mut Player, map: &SVMap) {
-        self.ticks += (args.dt * 1000.) as u32;
+            if let Some(r) = e.render_args() {
+                app.render(&r,
+                           player,
+                           &characters,
+                           &map.layers,
+                           &resolved_layers);
+                let view = (app.view_x, app.view_y);
+                let zoom = app.zoom;
+                let (render_ms, frame_ms) = app.frame_times();
+                let show_frame_times = *show_frame_times;
+                inspector.hovered = Some(app.tile_at(*cursor));
+                let inspected = if inspector.open { inspector.selected() } else { None };
+                let tile_rect = inspected.map_or([0.; 4], |pos| app.tile_rect(pos));
+                let ticks = app.ticks();
+                app.draw_overlay(&r, |c, gl| {
+                    runner.draw(font, &characters, player, view, zoom, c, gl);
+                    if browser.open {
+                        browser.draw(font, c, gl);
+                    }
+                    if info_panel.open {
+                        info_panel.draw(font, map_name, &map.properties, c, gl);
+                    }
+                    if let Some(pos) = inspected {
+                        inspector.draw(font, map, &resolved_layers, pos, tile_rect, ticks, c, gl);
+                    }
+                    if show_frame_times {
+                        let text = format!("render {:.2}ms, frame {:.2}ms", render_ms, frame_ms);
+                        let x = c.get_view_size()[0] - font.measure(&text) - 4.;
+                        font.draw(&text, (x, 4.), 1., [1.0, 1.0, 0.0, 1.0], c.transform, gl);
+                    }
+                });
+            }
 
-        if self.update_last_move {
-            self.update_last_move = false;
-            if self.a_pressed || self.d_pressed || self.s_pressed || self.w_pressed {
-                if self.w_pressed {
-                    player.dir = PlayerDir::Up;
-                }
-                if self.s_pressed {
-                    player.dir = PlayerDir::Down;
-                }
-                if self.a_pressed {
-                    player.dir = PlayerDir::Left;
+            if let Some(u) = e.update_args() {
+                if let Some(ref event) = event {
+                    runner.update(u.dt, event, &mut characters, player, app);
                 }
-                if self.d_pressed {
-                    player.dir = PlayerDir::Right;
+                app.update(&u, player, map, &resolved_layers);
+
+                if runner.take_warp_out() {
+                    if let Some(warp) = map.properties.warps.first() {
+                        return Some(Warp {
+                            from: warp.from,
+                            map: warp.map.clone(),
+                            to: warp.to,
+                        });
+                    }
                 }
-                player.last_move_start = Some(self.ticks);
-            } else {
-                player.last_move_start = None;
-            }
-        }
 
-        const MOVE_AMOUNT: f64 = 100.0;
-        let delta_x = if self.a_pressed {
-            -MOVE_AMOUNT * args.dt
-        } else if self.d_pressed {
-            MOVE_AMOUNT * args.dt
-        } else {
-            0.
-        };
-
-        let delta_y = if self.w_pressed {
-            -MOVE_AMOUNT * args.dt
-        } else if self.s_pressed {
-            MOVE_AMOUNT * args.dt
-        } else {
-            0.
-        };
-
-        let (adjusted_x, adjusted_y) = player.adjusted_pos(delta_x, delta_y);
-        let layer = map.layers.iter().find(|l| l.id == "Buildings").expect("no buildings?");
-        let mut clamp_to_current_pos = false;
-        for tile in &layer.tiles {
-            let (tx, ty) = tile.get_pos();
-            if (tx as i32, ty as i32) == (adjusted_x, adjusted_y + 1) {
-                let tilesheet = map.tilesheet(tile.get_tilesheet()).unwrap();
-                let passable = tilesheet.properties.tile_is_passable(tile.get_index(0)).unwrap_or(false);
-                clamp_to_current_pos = !passable;
-                break;
+                let tile = (player.x, player.y);
+                if tile != last_tile && !app.scripted {
+                    last_tile = tile;
+                    if let Some(warp) = map.properties.warp_at(tile) {
+                        return Some(Warp {
+                            from: warp.from,
+                            map: warp.map.clone(),
+                            to: warp.to,
+                        });
+                    }
+                }
             }
         }
-
-        player.move_horiz(delta_x, clamp_to_current_pos);
-        player.move_vert(delta_y, clamp_to_current_pos);
-
-        let player_x = player.x * 16 + player.offset_x as i32;
-        let player_y = player.y * 16 + player.offset_y as i32;
-
-        let (view_w, view_h) = ((self.view_w as f64 / SCALE) as i32, (self.view_h as f64 / SCALE)  as i32);
-
-        let adjusted_x = if player_x - self.view_x < view_w / 3 {
-            player_x - view_w / 3
-        } else if player_x - self.view_x > view_w / 3 * 2 {
-            player_x - view_w / 3 * 2
-        } else {
-            self.view_x
-        };
-
-        let adjusted_y = if player_y - self.view_y < view_h / 3 {
-            player_y - view_h / 3
-        } else if player_y - self.view_y > view_h / 3 * 2 {
-            player_y - view_h / 3 * 2
-        } else {
-            self.view_y
-        };
-
-        let max_x = (map.layers[0].size.0 as i32 - view_w / 16) * 16;
-        let max_y = (map.layers[0].size.1 as i32 - view_h / 16) * 16;
-        self.view_x = adjusted_x.max(0).min(max_x);
-        self.view_y = adjusted_y.max(0).min(max_y);
-    }
-}
-
-struct ScriptedCharacter {
-    name: String,
-    pos: (i32, i32),
-    dir: u8,
-}
-
-enum Command {
-    Pause(u32),
-    Emote(String, u8),
-    Move(String, (i32, i32), u8),
-    Speak(String, String),
-    GlobalFade,
-    Viewport(i32, i32),
-    Warp(String, (i32, i32)),
-    FaceDirection(String, u8),
-    ShowFrame(String, u32),
-    Speed(String, u8),
-    PlaySound(String),
-    Shake(String, u32),
-    Jump(String),
-    TextAboveHead(String, String),
-    AddQuest(u32),
-    Message(String),
-    Animate(String, bool, bool, u32, Vec<u32>),
-    StopAnimation(String),
-    Mail(String),
-    Friendship(String, i32),
-    PlayMusic(String),
-    SpecificTemporarySprite(String),
-    ChangeLocation(String),
-    ChangeToTemporaryMap(String),
-    Question(String, String),
-    Fork(String),
-    AmbientLight(u32, u32, u32),
-    PositionOffset(String, i32, i32),
-}
-
-enum Trigger {
-}
-
-#[allow(dead_code)]
-enum End {
-    WarpOut,
-    Dialogue(String, String),
-    Position((u32, u32)),
-    End,
-}
-
-struct ScriptedEvent {
-    _id: String,
-    _music: String,
-    viewport: (i32, i32),
-    characters: Vec<ScriptedCharacter>,
-    _skippable: bool,
-    _commands: Vec<Command>,
-    _end: End,
-    _triggers: Vec<Trigger>,
-    _forks: Vec<ScriptedEvent>,
-}
-
-fn parse_script(id: String, s: String) -> ScriptedEvent {
-    let mut forks = s.split('\n');
-    let mut parts = forks.next().unwrap().split('/');
-    let music = parts.next().unwrap().to_owned();
-    let viewport_str = parts.next().unwrap();
-    let mut viewport_str = viewport_str.split(' ');
-    let viewport = (viewport_str.next().unwrap().parse().unwrap(),
-                    viewport_str.next().unwrap().parse().unwrap());
-    let mut character_parts = parts.next().unwrap().split(' ').peekable();
-
-    let mut characters = vec![];
-    while character_parts.peek().is_some() {
-        let character = ScriptedCharacter {
-            name: character_parts.next().unwrap().to_owned(),
-            pos: (character_parts.next().unwrap().parse().unwrap(),
-                  character_parts.next().unwrap().parse().unwrap()),
-            dir: character_parts.next().unwrap().parse().unwrap(),
-        };
-        characters.push(character);
-    }
-
-    let mut peekable = parts.peekable();
-    let skippable = match peekable.peek() {
-        Some(&"skippable") => {
-            let _ = peekable.next();
-            true
-        }
-        Some(_) | None => false,
-    };
-
-    let mut commands = vec![];
-    for command_str in peekable {
-        let args: Vec<_> = command_str.split(' ').collect();
-        let command = match args[0] {
-            "pause" => Command::Pause(args[1].parse().unwrap()),
-            "emote" => Command::Emote(args[1].to_owned(), args[2].parse().unwrap()),
-            "move" => Command::Move(args[1].to_owned(),
-                                    (args[2].parse().unwrap(), args[3].parse().unwrap()),
-                                    args[4].parse().unwrap()),
-            "speak" => Command::Speak(args[1].to_owned(), args[2].to_owned()),
-            "globalFade" => Command::GlobalFade,
-            "viewport" => Command::Viewport(args[1].parse().unwrap(), args[2].parse().unwrap()),
-            "warp" => Command::Warp(args[1].to_owned(),
-                                    (args[2].parse().unwrap(), args[3].parse().unwrap())),
-            "faceDirection" => Command::FaceDirection(args[1].to_owned(), args[2].parse().unwrap()),
-            "showFrame" => Command::ShowFrame(args[1].to_owned(), args[2].parse().unwrap()),
-            "speed" => Command::Speed(args[1].to_owned(), args[2].parse().unwrap()),
-            "playSound" => Command::PlaySound(args[1].to_owned()),
-            "shake" => Command::Shake(args[1].to_owned(), args[2].parse().unwrap()),
-            "jump" => Command::Jump(args[1].to_owned()),
-            "textAboveHead" => Command::TextAboveHead(args[1].to_owned(), args[2].to_owned()),
-            "addQuest" => Command::AddQuest(args[1].parse().unwrap()),
-            "message" => Command::Message(args[1].to_owned()),
-            "animate" => Command::Animate(args[1].to_owned(),
-                                          args[2] == "t",
-                                          args[3] == "t",
-                                          args[4].parse().unwrap(),
-                                          args[5..].iter().map(|s| s.parse().unwrap()).collect()),
-            "stopAnimation" => Command::StopAnimation(args[1].to_owned()),
-            "mail" => Command::Mail(args[1].to_owned()),
-            "friendship" => Command::Friendship(args[1].to_owned(), args[2].parse().unwrap()),
-            "playMusic" => Command::PlayMusic(args[1].to_owned()),
-            "specificTemporarySprite" => Command::SpecificTemporarySprite(args[1].to_owned()),
-            "changeLocation" => Command::ChangeLocation(args[1].to_owned()),
-            "changeToTemporaryMap" => Command::ChangeToTemporaryMap(args[1].to_owned()),
-            "question" => Command::Question(args[1].to_owned(), args[2].to_owned()),
-            "fork" => Command::Fork(args[1].to_owned()),
-            "ambientLight" => Command::AmbientLight(args[1].parse().unwrap(),
-                                                    args[2].parse().unwrap(),
-                                                    args[3].parse().unwrap()),
-            "positionOffset" => Command::PositionOffset(args[1].to_owned(),
-                                                        args[2].parse().unwrap(),
-                                                        args[3].parse().unwrap()),
-            "end" => continue,
-            "" => continue,
-            s => panic!("unknown command {}", s),
-        };
-        commands.push(command);
-    }
-
-    ScriptedEvent {
-        _id: id,
-        _music: music,
-        viewport: viewport,
-        characters: characters,
-        _skippable: skippable,
-        _commands: commands,
-        _end: End::End, //XXXjdm
-        _triggers: vec![],
-        _forks: vec![],
-    }
-}
-
-fn characters_for_event(event: &ScriptedEvent, path: &Path) -> Vec<Character> {
-    let mut characters = vec![];
-    for character in &event.characters {
-        if character.name == "farmer" {
-            continue;
-        }
-        let texture = load_texture(path, &format!("{}.xnb", character.name));
-        let info = (texture, 0, (16, 32), (0, 0), [Some(0), Some(1), Some(2), Some(3)]);
-        characters.push(Character {
-            texture: info,
-            _name: character.name.clone(),
-            x: character.pos.0,
-            y: character.pos.1,
-            offset_x: 0.,
-            offset_y: 0.,
-            _index: 0,
-            dir: match character.dir {
-                0 => PlayerDir::Up,
-                1 => PlayerDir::Right,
-                2 => PlayerDir::Down,
-                3 => PlayerDir::Left,
-                _ => unreachable!(),
-            },
-        });
+        None
     }
-    characters
 }
 
-fn load_texture(base: &Path, filename: &str) -> Texture {
-    let mut f = File::open(base.join(filename)).unwrap();
-    let xnb = XNB::<Texture2d>::from_buffer(&mut f).unwrap();
-    let mut texture = xnb.primary;
-    let data = texture.mip_data.remove(0);
-    let data = match texture.format {
-        SurfaceFormat::Dxt3 => {
-            decompress_image(texture.width as i32,
-                             texture.height as i32,
-                             data.as_ptr() as *const _,
-                             CompressType::Dxt3)
-        }
-        _ => data,
-    };
-    let img = RgbaImage::from_raw(texture.width as u32,
-                                  texture.height as u32,
-                                  data).unwrap();
-    let mut settings = TextureSettings::new();
-    settings.set_filter(Filter::Nearest);
-    Texture::from_image(&img, &settings)
-}
-
-fn main() {
+fn view_main(assets: &AssetSource, map_name: String, event_id: Option<String>, at: Option<(i32, i32)>) {
     // Create an Glutin window.
     const WINDOW_DIMENSIONS: (u32, u32) = (800, 600);
-    let mut window: PistonWindow = WindowSettings::new(
+    let window: PistonWindow = WindowSettings::new(
             "spinning-square",
             [WINDOW_DIMENSIONS.0, WINDOW_DIMENSIONS.1]
         )
@@ -788,143 +454,51 @@ fn main() {
         .build()
         .unwrap();
 
-    let mut args = env::args();
-    let _self = args.next();
-    let map_name = args.next().unwrap_or("Town.xnb".into());
-    let event_id = args.next();
-
-    let mut view_x = args.next().and_then(|s| s.parse().ok()).unwrap_or(0);
-    let mut view_y = args.next().and_then(|s| s.parse().ok()).unwrap_or(0);
-
-    let base = Path::new("../xnb/uncompressed");
-    let mut f = File::open(base.join("Maps").join(&map_name)).unwrap();
-    let xnb = XNB::<SVMap>::from_buffer(&mut f).unwrap();
-    let mut map = xnb.primary;
-
-    for layer in &mut map.layers {
-        layer.tiles.sort_by(|t1, t2| {
-            let (t1_x, t1_y) = t1.get_pos();
-            let (t2_x, t2_y) = t2.get_pos();
-            t1_y.cmp(&t2_y).then_with(|| t1_x.cmp(&t2_x))
-        });
-    }
-
-    let event = event_id.and_then(|id| {
-        let f = File::open(base.join("Data/Events").join(&map_name)).ok();
-        let event = f.and_then(|mut f| {
-            let xnb = XNB::<Dictionary<String, String>>::from_buffer(&mut f).unwrap();
-            for (k, v) in &xnb.primary.map {
-                if k.split('/').next() == Some(&id) {
-                    return Some(v.clone());
-                }
-            }
-            None
-        });
-        if let Some(ref event) = event {
-            println!("got event source:\n{}", event);
-        }
-        event.map(|e| parse_script(id, e))
-    });
-
-    let mut tilesheets = HashMap::new();
-    for ts in &map.tilesheets {
-        let texture = load_texture(base, &format!("{}.xnb", ts.image_source));
-        println!("storing texture for {}", ts.id);
-        tilesheets.insert(ts.id.clone(), texture);
-    }
-    println!("loaded {} tilesheets", tilesheets.len());
-
-    let mut resolved_layers = vec![];
-    for layer in &map.layers {
-        let layer_tiles = layer.tiles.iter().map(|t| {
-            let name = t.get_tilesheet();
-            ResolvedTile {
-                texture: tilesheets.get(name).expect("missing texture"),
-                tilesheet: map.tilesheets.iter().find(|s| s.id == name).expect("missing tilesheet"),
-            }
-        }).collect();
-        resolved_layers.push(layer_tiles);
-    }
-
-    let character_path = Path::new("../xnb/uncompressed/Characters");
-    let path = character_path.join("Farmer");
-    let base = load_texture(&path, "farmer_base.xnb");
-    let bottom = load_texture(&path, "farmer_base.xnb");
-    let arms = load_texture(&path, "farmer_base.xnb");
-    let pants = load_texture(&path, "farmer_base.xnb");
-    let hairstyle = load_texture(&path, "hairstyles.xnb");
-    //let hat = load_texture(&path, "hats.xnb");
-    let shirt = load_texture(&path, "shirts.xnb");
-    let accessory = load_texture(&path, "accessories.xnb");
-    let base_dir_info = [Some(0), Some(2), Some(4), Some(2)];
-    let mut player = Player {
-        base: (base, 0, (16, 16), (0, 0), base_dir_info),
-        bottom: (bottom, 24, (16, 16), (0, 16), base_dir_info),
-        arms: (arms, 30, (16, 16), (0, 16), base_dir_info),
-        hairstyle: (hairstyle, 0, (16, 16), (0, 0), base_dir_info),
-        //hat: (hat, 2, (20, 20), (-2, -2), [Some(0), Some(1), Some(3), Some(2)]),
-        hat: None,
-        pants: (pants, 42, (16, 16), (0, 16), base_dir_info),
-        shirt: (shirt, 0, (8, 8), (4, 15), [Some(0), Some(1), Some(3), Some(2)]),
-        accessory: (accessory, 0, (16, 16), (0, 3), [Some(0), Some(1), None, Some(1)]),
-        x: 10,
-        y: 15,
-        offset_x: 0.,
-        offset_y: 0.,
-        last_move_start: None,
-        dir: PlayerDir::Down,
-    };
-
-    let characters = match event {
-        Some(ref ev) => characters_for_event(ev, &character_path),
-        None => vec![],
-    };
-
-    if let Some(ref event) = event {
-        view_x = event.viewport.0;
-        view_y = event.viewport.1;
-    }
+    let mut locations = LocationCache::new(CACHED_LOCATIONS);
+    let tile_size = or_exit(locations.get(assets, &map_name)).map.tilesheets[0].tile_size;
+    let (view_x, view_y) = at.unwrap_or((0, 0));
 
     // Create a new game and run it.
-    let mut app = App {
-        gl: GlGraphics::new(OpenGL::V3_2),
-        view_x: view_x * map.tilesheets[0].tile_size.0 as i32,
-        view_y: view_y * map.tilesheets[0].tile_size.1 as i32,
-        view_w: WINDOW_DIMENSIONS.0,
-        view_h: WINDOW_DIMENSIONS.1,
-        ticks: 0,
-        a_pressed: false,
-        d_pressed: false,
-        w_pressed: false,
-        s_pressed: false,
-        update_last_move: false,
+    let mut viewer = Viewer {
+        assets: assets,
+        window: window,
+        app: App::new(OpenGL::V3_2,
+                      (view_x * tile_size.0 as i32,
+                       view_y * tile_size.1 as i32),
+                      WINDOW_DIMENSIONS),
+        font: or_exit(Font::load(assets, "SmallFont.xnb")),
+        player: or_exit(Player::load(assets)),
+        info_panel: InfoPanel::new(),
+        inspector: TileInspector::new(),
+        cursor: (0., 0.),
+        drag_start: None,
+        dragged: false,
+        show_frame_times: false,
     };
 
-    while let Some(e) = window.next() {
-        if let Some(Button::Keyboard(k)) = e.press_args() {
-            match k {
-                Key::Left if app.view_x > 0 => app.view_x -= 1,
-                Key::Right => app.view_x += 1,
-                Key::Up if app.view_y > 0 => app.view_y -= 1,
-                Key::Down => app.view_y += 1,
-                k => app.key_pressed(k),
-            }
-        }
-
-        if let Some(Button::Keyboard(k)) = e.release_args() {
-            app.key_released(k);
-        }
-
-        if let Some(r) = e.render_args() {
-            app.render(&r,
-                       &player,
-                       &characters,
-                       &map.layers,
-                       &resolved_layers);
-        }
+    let mut map_name = map_name;
+    let mut event_id = event_id;
+    loop {
+        let warp = {
+            let location = or_exit(locations.get(assets, &map_name));
+            viewer.run_location(location, event_id.take())
+        };
+        let warp = match warp {
+            Some(warp) => warp,
+            None => return,
+        };
 
-        if let Some(u) = e.update_args() {
-            app.update(&u, &mut player, &map);
+        let target = map_file(assets, &warp.map, &map_name);
+        match locations.get(assets, &target) {
+            Ok(location) => {
+                viewer.player.set_position(warp.to, (0., 0.));
+                viewer.app.center_on(warp.to, location.map.layers[0].size);
+                map_name = target;
+            }
+            Err(e) => {
+                // Stay put, just off the broken warp.
+                println!("couldn't follow warp to {}: {}", warp.map, e);
+            }
         }
     }
 }
@@ -0,0 +1,708 @@
+use assets::{AssetError, AssetSource};
+use opengl_graphics::Texture;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use tbin::read_tbin;
+use texture::{TextureError, load_texture};
+use tmx::read_tmx;
+use xnb::XNB;
+use xnb::tide::{Layer, Tile, TileSheet, Map, PropertyValue, PropertyParse};
+
+pub type SVMap = Map<MapProps, TilesetProps, LayerProps, TileProps>;
+
+pub struct LayerProps;
+impl PropertyParse for LayerProps {
+    fn parse(_props: Vec<(String, PropertyValue)>) -> Self {
+        LayerProps
+    }
+}
+
+/// A `Warp` entry: walking onto `from` takes the farmer to `to` on `map`.
+pub struct Warp {
+    pub from: (i32, i32),
+    pub map: String,
+    pub to: (i32, i32),
+}
+
+/// A `Doors` entry: a door at `pos` drawn with tile `index` of `sheet`.
+pub struct Door {
+    pub pos: (i32, i32),
+    pub sheet: String,
+    pub index: u32,
+}
+
+/// A `Light` entry: a light source of type `kind` at `pos`.
+pub struct Light {
+    pub pos: (i32, i32),
+    pub kind: u32,
+}
+
+/// A `DayTiles` or `NightTiles` entry: the tile on `layer` at `pos` is
+/// replaced with `index` from the same tilesheet.
+pub struct TileChange {
+    pub layer: String,
+    pub pos: (i32, i32),
+    pub index: u32,
+}
+
+/// The map properties the game uses to wire up a location.
+pub struct MapProps {
+    pub warps: Vec<Warp>,
+    pub doors: Vec<Door>,
+    pub lights: Vec<Light>,
+    pub music: Option<String>,
+    pub outdoors: bool,
+    pub treat_as_outdoors: bool,
+    pub viewport_follow_player: bool,
+    pub day_tiles: Vec<TileChange>,
+    pub night_tiles: Vec<TileChange>,
+    /// Properties without a field of their own, by name.
+    pub other: Vec<(String, String)>,
+}
+
+/// The text of a property value, however it was stored.
+fn property_text(value: PropertyValue) -> String {
+    match value {
+        PropertyValue::String(s) => s,
+        PropertyValue::Bool(b) => (if b { "T" } else { "F" }).to_owned(),
+        PropertyValue::Int(i) => i.to_string(),
+        PropertyValue::Float(f) => f.to_string(),
+    }
+}
+
+/// Split a property's value into groups of `size` space-separated fields and
+/// parse each complete group with `parse`, skipping any that don't parse.
+fn property_groups<T, F: Fn(&[&str]) -> Option<T>>(value: &str, size: usize, parse: F) -> Vec<T> {
+    let fields: Vec<_> = value.split_whitespace().collect();
+    fields.chunks(size).filter(|g| g.len() == size).filter_map(|g| parse(g)).collect()
+}
+
+fn parse_tile_changes(value: &str) -> Vec<TileChange> {
+    property_groups(value, 4, |g| Some(TileChange {
+        layer: g[0].to_owned(),
+        pos: (g[1].parse().ok()?, g[2].parse().ok()?),
+        index: g[3].parse().ok()?,
+    }))
+}
+
+fn tile_changes_text(changes: &[TileChange]) -> String {
+    changes.iter()
+        .map(|c| format!("{} {} {} {}", c.layer, c.pos.0, c.pos.1, c.index))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl MapProps {
+    /// The warp that walking onto `pos` triggers, if any.
+    pub fn warp_at(&self, pos: (i32, i32)) -> Option<&Warp> {
+        self.warps.iter().find(|w| w.from == pos)
+    }
+
+    /// Every property as its name and text, in the form they are stored in
+    /// the map.
+    pub fn properties(&self) -> Vec<(String, String)> {
+        let mut props = vec![];
+        {
+            let mut text = |name: &str, value: String| props.push((name.to_owned(), value));
+            if !self.warps.is_empty() {
+                text("Warp", self.warps.iter()
+                     .map(|w| format!("{} {} {} {} {}", w.from.0, w.from.1, w.map, w.to.0, w.to.1))
+                     .collect::<Vec<_>>()
+                     .join(" "));
+            }
+            if !self.doors.is_empty() {
+                text("Doors", self.doors.iter()
+                     .map(|d| format!("{} {} {} {}", d.pos.0, d.pos.1, d.sheet, d.index))
+                     .collect::<Vec<_>>()
+                     .join(" "));
+            }
+            if !self.lights.is_empty() {
+                text("Light", self.lights.iter()
+                     .map(|l| format!("{} {} {}", l.pos.0, l.pos.1, l.kind))
+                     .collect::<Vec<_>>()
+                     .join(" "));
+            }
+            if let Some(ref music) = self.music {
+                text("Music", music.clone());
+            }
+            if self.outdoors {
+                text("Outdoors", "T".to_owned());
+            }
+            if self.treat_as_outdoors {
+                text("TreatAsOutdoors", "T".to_owned());
+            }
+            if self.viewport_follow_player {
+                text("ViewportFollowPlayer", "T".to_owned());
+            }
+            if !self.day_tiles.is_empty() {
+                text("DayTiles", tile_changes_text(&self.day_tiles));
+            }
+            if !self.night_tiles.is_empty() {
+                text("NightTiles", tile_changes_text(&self.night_tiles));
+            }
+        }
+        props.extend(self.other.iter().cloned());
+        props
+    }
+}
+
+impl PropertyParse for MapProps {
+    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
+        let mut map_props = MapProps {
+            warps: vec![],
+            doors: vec![],
+            lights: vec![],
+            music: None,
+            outdoors: false,
+            treat_as_outdoors: false,
+            viewport_follow_player: false,
+            day_tiles: vec![],
+            night_tiles: vec![],
+            other: vec![],
+        };
+        for (k, v) in props {
+            let value = property_text(v);
+            match k.as_ref() {
+                "Warp" => map_props.warps = property_groups(&value, 5, |g| Some(Warp {
+                    from: (g[0].parse().ok()?, g[1].parse().ok()?),
+                    map: g[2].to_owned(),
+                    to: (g[3].parse().ok()?, g[4].parse().ok()?),
+                })),
+                "Doors" => map_props.doors = property_groups(&value, 4, |g| Some(Door {
+                    pos: (g[0].parse().ok()?, g[1].parse().ok()?),
+                    sheet: g[2].to_owned(),
+                    index: g[3].parse().ok()?,
+                })),
+                "Light" => map_props.lights = property_groups(&value, 3, |g| Some(Light {
+                    pos: (g[0].parse().ok()?, g[1].parse().ok()?),
+                    kind: g[2].parse().ok()?,
+                })),
+                "Music" => map_props.music = Some(value),
+                "Outdoors" => map_props.outdoors = value != "F",
+                "TreatAsOutdoors" => map_props.treat_as_outdoors = value != "F",
+                "ViewportFollowPlayer" => map_props.viewport_follow_player = value != "F",
+                "DayTiles" => map_props.day_tiles = parse_tile_changes(&value),
+                "NightTiles" => map_props.night_tiles = parse_tile_changes(&value),
+                _ => map_props.other.push((k, value)),
+            }
+        }
+        map_props
+    }
+}
+
+pub struct TileProps {
+    pub passable: Option<bool>,
+    /// What happens when the farmer interacts with the tile, such as
+    /// `Warp 6 29 Town`.
+    pub action: Option<String>,
+    /// Properties without a field of their own, such as `TouchAction`, by
+    /// name.
+    pub other: Vec<(String, String)>,
+}
+impl PropertyParse for TileProps {
+    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
+        let mut passable = None;
+        let mut action = None;
+        let mut other = vec![];
+        for (k, v) in props {
+            match (k.as_ref(), v) {
+                ("Passable", PropertyValue::String(b)) => passable = Some(b == "T"),
+                ("Action", PropertyValue::String(a)) => action = Some(a),
+                (_, v) => other.push((k.clone(), property_text(v))),
+            }
+        }
+
+        Self {
+            passable: passable,
+            action: action,
+            other: other,
+        }
+    }
+}
+
+impl TileProps {
+    /// Every property as its name and text, in the form they are stored in
+    /// the map.
+    pub fn properties(&self) -> Vec<(String, String)> {
+        let mut props = vec![];
+        if let Some(b) = self.passable {
+            props.push(("Passable".to_owned(), flag_text(b)));
+        }
+        if let Some(ref action) = self.action {
+            props.push(("Action".to_owned(), action.clone()));
+        }
+        props.extend(self.other.iter().cloned());
+        props
+    }
+
+    /// The warp triggered by interacting with the tile at `pos`, if its
+    /// action is a `Warp` or `LockedDoorWarp`.
+    pub fn action_warp(&self, pos: (i32, i32)) -> Option<Warp> {
+        let action = self.action.as_ref()?;
+        let args: Vec<_> = action.split_whitespace().collect();
+        match args.first() {
+            Some(&"Warp") | Some(&"LockedDoorWarp") if args.len() >= 4 => Some(Warp {
+                from: pos,
+                map: args[3].to_owned(),
+                to: (args[1].parse().ok()?, args[2].parse().ok()?),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The properties of a static or animated tile.
+pub fn tile_props(tile: &Tile<TileProps>) -> &TileProps {
+    match *tile {
+        Tile::Static(ref t) => &t.properties,
+        Tile::Animated(ref t) => &t.properties,
+    }
+}
+
+pub enum TileType {
+    Grass,
+    Stone,
+    Dirt,
+    Wood,
+    /// A type the viewer doesn't know about.
+    Other(String),
+}
+
+impl TileType {
+    /// The name used for this type in a `TileIndex@<n>@Type` property.
+    pub fn name(&self) -> &str {
+        match *self {
+            TileType::Grass => "Grass",
+            TileType::Stone => "Stone",
+            TileType::Dirt => "Dirt",
+            TileType::Wood => "Wood",
+            TileType::Other(ref name) => name,
+        }
+    }
+}
+
+/// The `TileIndex@<n>@<name>` properties of a tilesheet, each as a list of
+/// the tile indices it is set for and its value.
+pub struct TilesetProps {
+    pub passable: Vec<(u32, bool)>,
+    pub water: Vec<(u32, bool)>,
+    pub types: Vec<(u32, TileType)>,
+    pub spawnable: Vec<(u32, bool)>,
+    pub diggable: Vec<(u32, bool)>,
+    pub shadow: Vec<(u32, bool)>,
+    pub path_types: Vec<(u32, String)>,
+    /// Properties without a field of their own, by tile index and name.
+    pub other: BTreeMap<(u32, String), String>,
+}
+
+fn lookup<T>(values: &[(u32, T)], idx: u32) -> Option<&T> {
+    values.iter().find(|&&(i, _)| i == idx).map(|&(_, ref v)| v)
+}
+
+fn flag_text(b: bool) -> String {
+    (if b { "T" } else { "F" }).to_owned()
+}
+
+impl TilesetProps {
+    pub fn tile_is_passable(&self, idx: u32) -> Option<bool> {
+        lookup(&self.passable, idx).cloned()
+    }
+
+    pub fn tile_is_water(&self, idx: u32) -> Option<bool> {
+        lookup(&self.water, idx).cloned()
+    }
+
+    pub fn tile_type(&self, idx: u32) -> Option<&TileType> {
+        lookup(&self.types, idx)
+    }
+
+    pub fn tile_is_spawnable(&self, idx: u32) -> Option<bool> {
+        lookup(&self.spawnable, idx).cloned()
+    }
+
+    pub fn tile_is_diggable(&self, idx: u32) -> Option<bool> {
+        lookup(&self.diggable, idx).cloned()
+    }
+
+    pub fn tile_has_shadow(&self, idx: u32) -> Option<bool> {
+        lookup(&self.shadow, idx).cloned()
+    }
+
+    pub fn tile_path_type(&self, idx: u32) -> Option<&str> {
+        lookup(&self.path_types, idx).map(|p| &p[..])
+    }
+
+    /// The properties of tile `idx` that the viewer doesn't understand.
+    pub fn tile_other<'a>(&'a self, idx: u32) -> Box<Iterator<Item = (&'a str, &'a str)> + 'a> {
+        Box::new(self.other.iter()
+                 .filter(move |&(&(i, _), _)| i == idx)
+                 .map(|(&(_, ref name), value)| (&name[..], &value[..])))
+    }
+
+    /// Every property set for tile `idx` as its name and text, in the form
+    /// they are stored in the tilesheet.
+    pub fn tile_properties(&self, idx: u32) -> Vec<(String, String)> {
+        let mut props = vec![];
+        {
+            let mut flag = |name: &str, value: Option<bool>| {
+                if let Some(b) = value {
+                    props.push((name.to_owned(), flag_text(b)));
+                }
+            };
+            flag("Passable", self.tile_is_passable(idx));
+            flag("Water", self.tile_is_water(idx));
+            flag("Spawnable", self.tile_is_spawnable(idx));
+            flag("Diggable", self.tile_is_diggable(idx));
+            flag("Shadow", self.tile_has_shadow(idx));
+        }
+        if let Some(kind) = self.tile_type(idx) {
+            props.push(("Type".to_owned(), kind.name().to_owned()));
+        }
+        if let Some(path) = self.tile_path_type(idx) {
+            props.push(("PathType".to_owned(), path.to_owned()));
+        }
+        props.extend(self.tile_other(idx).map(|(k, v)| (k.to_owned(), v.to_owned())));
+        props
+    }
+
+    /// The indices of every tile with at least one property, in order.
+    pub fn indices(&self) -> BTreeSet<u32> {
+        let mut indices = BTreeSet::new();
+        {
+            let mut flags = |values: &[(u32, bool)]| indices.extend(values.iter().map(|&(i, _)| i));
+            flags(&self.passable);
+            flags(&self.water);
+            flags(&self.spawnable);
+            flags(&self.diggable);
+            flags(&self.shadow);
+        }
+        indices.extend(self.types.iter().map(|&(i, _)| i));
+        indices.extend(self.path_types.iter().map(|&(i, _)| i));
+        indices.extend(self.other.keys().map(|&(i, _)| i));
+        indices
+    }
+}
+
+impl PropertyParse for TilesetProps {
+    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
+        let mut tileset = TilesetProps {
+            passable: vec![],
+            water: vec![],
+            types: vec![],
+            spawnable: vec![],
+            diggable: vec![],
+            shadow: vec![],
+            path_types: vec![],
+            other: BTreeMap::new(),
+        };
+
+        for (k, v) in props {
+            let mut parts = k.splitn(4, '@');
+            let _ = parts.next();
+            if parts.next() != Some("TileIndex") {
+                continue;
+            }
+            let idx = match parts.next().and_then(|i| i.parse().ok()) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let name = parts.next().unwrap_or("");
+            let value = property_text(v);
+            match name {
+                "Passable" => tileset.passable.push((idx, value == "T")),
+                "Water" => tileset.water.push((idx, value == "T")),
+                "Spawnable" => tileset.spawnable.push((idx, value == "T")),
+                "Diggable" => tileset.diggable.push((idx, value == "T")),
+                "Shadow" => tileset.shadow.push((idx, value == "T")),
+                "PathType" => tileset.path_types.push((idx, value)),
+                "Type" => tileset.types.push((idx, match value.as_ref() {
+                    "Dirt" => TileType::Dirt,
+                    "Stone" => TileType::Stone,
+                    "Grass" => TileType::Grass,
+                    "Wood" => TileType::Wood,
+                    _ => TileType::Other(value),
+                })),
+                name => {
+                    let _ = tileset.other.insert((idx, name.to_owned()), value);
+                }
+            }
+        }
+        tileset
+    }
+}
+
+pub struct ResolvedTile<'a> {
+    pub texture: &'a Texture,
+    pub tilesheet: &'a TileSheet<TilesetProps>,
+    /// The index of the tile in its layer's `tiles`.
+    pub tile: usize,
+}
+
+/// A layer's contents laid out in a dense `width * height` grid, so the tile
+/// at a position can be found without searching the layer.
+pub struct TileGrid<T> {
+    pub size: (u32, u32),
+    cells: Vec<Option<T>>,
+}
+
+impl<T> TileGrid<T> {
+    pub fn new<I: IntoIterator<Item = ((u32, u32), T)>>(size: (u32, u32), tiles: I) -> TileGrid<T> {
+        let mut cells: Vec<_> = (0..size.0 * size.1).map(|_| None).collect();
+        for ((x, y), tile) in tiles {
+            if x < size.0 && y < size.1 {
+                cells[(y * size.0 + x) as usize] = Some(tile);
+            }
+        }
+        TileGrid {
+            size: size,
+            cells: cells,
+        }
+    }
+
+    /// The tile at `(x, y)`, if there is one and the position is on the map.
+    pub fn get(&self, x: i32, y: i32) -> Option<&T> {
+        if x < 0 || y < 0 || x as u32 >= self.size.0 || y as u32 >= self.size.1 {
+            return None;
+        }
+        self.cells[(y as u32 * self.size.0 + x as u32) as usize].as_ref()
+    }
+
+    /// The tiles within the inclusive tile rectangle from `(x0, y0)` to
+    /// `(x1, y1)` with their positions, in (y, x) order. Only the rows and
+    /// columns inside the rectangle are visited.
+    pub fn region<'a>(&'a self,
+                      (x0, y0): (i32, i32),
+                      (x1, y1): (i32, i32)) -> Box<Iterator<Item = ((i32, i32), &'a T)> + 'a> {
+        let (w, h) = (self.size.0 as i32, self.size.1 as i32);
+        let (x0, x1) = (x0.max(0), x1.min(w - 1));
+        let (y0, y1) = (y0.max(0), y1.min(h - 1));
+        if x0 > x1 || y0 > y1 {
+            return Box::new(None.into_iter());
+        }
+        Box::new((y0..y1 + 1).flat_map(move |y| {
+            let row = &self.cells[(y * w + x0) as usize..(y * w + x1 + 1) as usize];
+            row.iter().enumerate().filter_map(move |(i, cell)| {
+                cell.as_ref().map(|tile| ((x0 + i as i32, y), tile))
+            })
+        }))
+    }
+
+    /// Every tile in the grid with its position, in (y, x) order.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = ((i32, i32), &'a T)> + 'a> {
+        let width = self.size.0 as usize;
+        Box::new(self.cells.iter().enumerate().filter_map(move |(i, cell)| {
+            cell.as_ref().map(|tile| (((i % width) as i32, (i / width) as i32), tile))
+        }))
+    }
+}
+
+/// Index `layer` by position, storing the index of each tile in `tiles`.
+pub fn grid_for_layer(layer: &Layer<LayerProps, TileProps>) -> TileGrid<usize> {
+    TileGrid::new(layer.size, layer.tiles.iter().enumerate().map(|(i, t)| (t.get_pos(), i)))
+}
+
+/// Why a map couldn't be loaded.
+#[derive(Debug)]
+pub enum MapError {
+    Missing(AssetError),
+    /// A map file couldn't be read.
+    Invalid(String, io::Error),
+    /// A tile refers to a tilesheet the map doesn't have, or whose texture
+    /// wasn't loaded.
+    UnknownTilesheet(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::Missing(ref e) => write!(f, "{}", e),
+            MapError::Invalid(ref name, ref e) => write!(f, "couldn't read map {}: {}", name, e),
+            MapError::UnknownTilesheet(ref name) => write!(f, "tile uses unknown tilesheet {}", name),
+        }
+    }
+}
+
+impl Error for MapError {
+    fn description(&self) -> &str {
+        match *self {
+            MapError::Missing(_) => "map not found",
+            MapError::Invalid(..) => "invalid map",
+            MapError::UnknownTilesheet(_) => "unknown tilesheet",
+        }
+    }
+}
+
+impl From<AssetError> for MapError {
+    fn from(e: AssetError) -> MapError {
+        MapError::Missing(e)
+    }
+}
+
+/// Load `map_name` from `Maps`. Maps saved by Tiled (`.tmx`) and tIDE
+/// (`.tbin`) are read directly; anything else is expected to be an XNB.
+pub fn load_map(assets: &AssetSource, map_name: &str) -> Result<SVMap, MapError> {
+    let mut f = assets.open(format!("Maps/{}", map_name))?;
+    let mut map = if map_name.ends_with(".tmx") {
+        read_tmx(f).map_err(|e| MapError::Invalid(map_name.to_owned(), e))?
+    } else if map_name.ends_with(".tbin") {
+        read_tbin(io::BufReader::new(f)).map_err(|e| MapError::Invalid(map_name.to_owned(), e))?
+    } else {
+        XNB::<SVMap>::from_buffer(&mut f)
+            .map_err(|e| MapError::Invalid(map_name.to_owned(),
+                                           io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))))?
+            .primary
+    };
+
+    for layer in &mut map.layers {
+        layer.tiles.sort_by(|t1, t2| {
+            let (t1_x, t1_y) = t1.get_pos();
+            let (t2_x, t2_y) = t2.get_pos();
+            t1_y.cmp(&t2_y).then_with(|| t1_x.cmp(&t2_x))
+        });
+    }
+    Ok(map)
+}
+
+/// The formats `load_map` can read, by file extension.
+const MAP_EXTENSIONS: [&str; 3] = ["xnb", "tmx", "tbin"];
+
+/// The file in `Maps` for the map `name`, as named by a warp out of the map
+/// file `from`. A name with an extension is used as is; otherwise a map in
+/// the same format as `from` is preferred, then XNB, TMX and tBIN.
+pub fn map_file(assets: &AssetSource, name: &str, from: &str) -> String {
+    if MAP_EXTENSIONS.iter().any(|ext| name.ends_with(&format!(".{}", ext))) {
+        return name.to_owned();
+    }
+    let from_ext = MAP_EXTENSIONS.iter().find(|ext| from.ends_with(&format!(".{}", ext)));
+    from_ext.into_iter()
+        .chain(MAP_EXTENSIONS.iter())
+        .map(|ext| format!("{}.{}", name, ext))
+        .find(|file| assets.exists(format!("Maps/{}", file)))
+        .unwrap_or_else(|| format!("{}.xnb", name))
+}
+
+pub fn load_tilesheets(map: &SVMap, assets: &AssetSource) -> Result<HashMap<String, Texture>, TextureError> {
+    let mut tilesheets = HashMap::new();
+    for ts in &map.tilesheets {
+        let texture = load_texture(assets, &format!("{}.xnb", ts.image_source))?;
+        tilesheets.insert(ts.id.clone(), texture);
+    }
+    Ok(tilesheets)
+}
+
+pub fn resolve_layers<'a>(map: &'a SVMap,
+                          tilesheets: &'a HashMap<String, Texture>)
+                          -> Result<Vec<TileGrid<ResolvedTile<'a>>>, MapError> {
+    let mut resolved_layers = vec![];
+    for layer in &map.layers {
+        let mut layer_tiles = Vec::with_capacity(layer.tiles.len());
+        for (i, t) in layer.tiles.iter().enumerate() {
+            let name = t.get_tilesheet();
+            let unknown = || MapError::UnknownTilesheet(name.to_owned());
+            layer_tiles.push((t.get_pos(), ResolvedTile {
+                texture: tilesheets.get(name).ok_or_else(&unknown)?,
+                tilesheet: map.tilesheets.iter().find(|s| s.id == name).ok_or_else(&unknown)?,
+                tile: i,
+            }));
+        }
+        resolved_layers.push(TileGrid::new(layer.size, layer_tiles));
+    }
+    Ok(resolved_layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xnb::tide::StaticTile;
+
+    fn layer(size: (u32, u32), positions: &[(u32, u32)]) -> Layer<LayerProps, TileProps> {
+        Layer {
+            id: "Buildings".to_owned(),
+            visible: true,
+            description: String::new(),
+            size: size,
+            tile_size: (16, 16),
+            properties: LayerProps,
+            tiles: positions.iter().enumerate().map(|(i, &pos)| Tile::Static(StaticTile {
+                pos: pos,
+                tilesheet: "outdoors".to_owned(),
+                index: i as u32,
+                blend_mode: 0,
+                properties: TileProps::parse(vec![]),
+            })).collect(),
+        }
+    }
+
+    #[test]
+    fn grid_lookup_matches_linear_scan() {
+        let layer = layer((5, 4), &[(0, 0), (4, 0), (2, 1), (3, 3), (7, 1)]);
+        let grid = grid_for_layer(&layer);
+        for y in -1..5 {
+            for x in -1..6 {
+                let scanned = layer.tiles.iter().position(|t| {
+                    x >= 0 && y >= 0 && x < 5 && y < 4 && t.get_pos() == (x as u32, y as u32)
+                });
+                assert_eq!(grid.get(x, y).cloned(), scanned, "at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn region_is_clipped_to_the_grid() {
+        let grid = TileGrid::new((3, 3), (0..9).map(|i| ((i % 3, i / 3), i)));
+        let region = |from, to| grid.region(from, to).map(|(pos, &i)| (pos, i)).collect::<Vec<_>>();
+
+        assert_eq!(region((-5, -5), (0, 0)), [((0, 0), 0)]);
+        assert_eq!(region((2, 1), (10, 2)), [((2, 1), 5), ((2, 2), 8)]);
+        assert_eq!(region((1, 1), (1, 1)), [((1, 1), 4)]);
+        assert!(region((3, 0), (5, 2)).is_empty());
+        assert!(region((2, 2), (1, 1)).is_empty());
+        assert_eq!(region((-1, -1), (3, 3)).len(), 9);
+    }
+
+    #[test]
+    fn unknown_map_properties_are_kept() {
+        let props = MapProps::parse(vec![
+            ("Warp".to_owned(), PropertyValue::String("1 2 Town 3 4 5".to_owned())),
+            ("Light".to_owned(), PropertyValue::String("3 4 1".to_owned())),
+            ("Fall_Objects".to_owned(), PropertyValue::String("T".to_owned())),
+        ]);
+        assert_eq!(props.warps.len(), 1);
+        assert_eq!(props.warps[0].to, (3, 4));
+        assert_eq!(props.properties(), [
+            ("Warp".to_owned(), "1 2 Town 3 4".to_owned()),
+            ("Light".to_owned(), "3 4 1".to_owned()),
+            ("Fall_Objects".to_owned(), "T".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn warp_targets_are_found_in_any_format() {
+        use std::fs::{self, File};
+        use std::path::PathBuf;
+        use std::{env, process};
+
+        /// Removes the scratch asset root however the test ends.
+        struct Scratch(PathBuf);
+        impl Drop for Scratch {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.0);
+            }
+        }
+
+        let root = Scratch(env::temp_dir().join(format!("sv-map-viewer-map-file-{}", process::id())));
+        fs::create_dir_all(root.0.join("Maps")).unwrap();
+        for file in &["Town.xnb", "Town.tmx", "Cave.tbin"] {
+            File::create(root.0.join("Maps").join(file)).unwrap();
+        }
+        let assets = AssetSource::new(vec![root.0.clone()]);
+
+        assert_eq!(map_file(&assets, "Town", "Farm.xnb"), "Town.xnb");
+        assert_eq!(map_file(&assets, "Town", "Farm.tmx"), "Town.tmx");
+        assert_eq!(map_file(&assets, "Cave", "Farm.xnb"), "Cave.tbin");
+        assert_eq!(map_file(&assets, "Town.tmx", "Farm.xnb"), "Town.tmx");
+        assert_eq!(map_file(&assets, "Nowhere", "Farm.tmx"), "Nowhere.xnb");
+    }
+}
@@ -0,0 +1,97 @@
+use map::{TileType, TilesetProps};
+
+const PASSABLE: [f32; 4] = [0.0, 1.0, 0.0, 0.25];
+const BLOCKED: [f32; 4] = [1.0, 0.0, 0.0, 0.4];
+const WATER: [f32; 4] = [0.0, 0.4, 1.0, 0.45];
+const SPAWNABLE: [f32; 4] = [1.0, 0.9, 0.0, 0.4];
+const DIGGABLE: [f32; 4] = [0.5, 0.25, 0.0, 0.5];
+const SHADOW: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const PATH_TYPE: [f32; 4] = [1.0, 0.5, 0.0, 0.45];
+const OTHER: [f32; 4] = [1.0, 0.0, 1.0, 0.45];
+
+fn type_color(kind: &TileType) -> [f32; 4] {
+    match *kind {
+        TileType::Grass => [0.3, 0.9, 0.2, 0.4],
+        TileType::Stone => [0.6, 0.6, 0.65, 0.4],
+        TileType::Dirt => [0.6, 0.4, 0.2, 0.4],
+        TileType::Wood => [0.9, 0.6, 0.3, 0.4],
+        TileType::Other(_) => [1.0, 1.0, 1.0, 0.4],
+    }
+}
+
+/// Debug overlays that tint each tile of a map by its collision and terrain
+/// data.
+#[derive(Clone, Copy, Default)]
+pub struct Overlays {
+    /// Whether the farmer can walk onto the tile.
+    pub passable: bool,
+    /// Tiles the tilesheet marks as water.
+    pub water: bool,
+    /// The tile's `Type`, such as grass or stone.
+    pub types: bool,
+    /// Tiles where forage and artifact spots can appear.
+    pub spawnable: bool,
+    /// Tiles that can be hoed.
+    pub diggable: bool,
+    /// Tiles that cast a shadow.
+    pub shadow: bool,
+    /// Tiles with a `PathType`.
+    pub path_types: bool,
+    /// Tiles with properties the viewer doesn't understand.
+    pub other: bool,
+}
+
+impl Overlays {
+    pub fn any(&self) -> bool {
+        self.passable || self.water || self.types || self.spawnable || self.diggable ||
+            self.shadow || self.path_types || self.other
+    }
+
+    /// The colors to tint a map position with, bottom first, given the
+    /// tilesheet properties and index of its Back and Buildings tiles.
+    /// Passability follows the same rule as the farmer's collision check: a
+    /// Buildings tile blocks movement unless its tilesheet says otherwise.
+    pub fn tints(&self,
+                 back: Option<(&TilesetProps, u32)>,
+                 buildings: Option<(&TilesetProps, u32)>) -> Vec<[f32; 4]> {
+        let mut tints = vec![];
+        if self.types {
+            if let Some(kind) = back.and_then(|(props, index)| props.tile_type(index)) {
+                tints.push(type_color(kind));
+            }
+        }
+        let back_flag = |f: fn(&TilesetProps, u32) -> Option<bool>| {
+            back.and_then(|(props, index)| f(props, index)).unwrap_or(false)
+        };
+        if self.water && back_flag(TilesetProps::tile_is_water) {
+            tints.push(WATER);
+        }
+        if self.spawnable && back_flag(TilesetProps::tile_is_spawnable) {
+            tints.push(SPAWNABLE);
+        }
+        if self.diggable && back_flag(TilesetProps::tile_is_diggable) {
+            tints.push(DIGGABLE);
+        }
+        if self.shadow && back_flag(TilesetProps::tile_has_shadow) {
+            tints.push(SHADOW);
+        }
+        if self.path_types && back.and_then(|(props, index)| props.tile_path_type(index)).is_some() {
+            tints.push(PATH_TYPE);
+        }
+        if self.other {
+            let has_other = |tile: Option<(&TilesetProps, u32)>| {
+                tile.map_or(false, |(props, index)| props.tile_other(index).next().is_some())
+            };
+            if has_other(back) || has_other(buildings) {
+                tints.push(OTHER);
+            }
+        }
+        if self.passable {
+            let passable = buildings.map_or(true, |(props, index)| {
+                props.tile_is_passable(index).unwrap_or(false)
+            });
+            tints.push(if passable { PASSABLE } else { BLOCKED });
+        }
+        tints
+    }
+}
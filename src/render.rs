@@ -0,0 +1,90 @@
+use character::PlayerDir;
+use graphics::Image;
+use map::TilesetProps;
+use opengl_graphics::ImageSize;
+use texture::TextureTileInfo;
+use xnb::tide::TileSheet;
+
+pub struct Tile<'a> {
+    pub sheet: &'a TileSheet<TilesetProps>,
+    pub index: u32,
+}
+
+pub fn image_for_tile(tile: &Tile, pos: (i32, i32), view: (i32, i32)) -> Image {
+    let num_h_tiles = tile.sheet.sheet_size.0;
+    let tile_w = tile.sheet.tile_size.0;
+    let tile_h = tile.sheet.tile_size.1;
+    image_for_tile_reference(num_h_tiles,
+                             (tile_w, tile_h),
+                             tile.index,
+                             0,
+                             pos,
+                             (0, 0),
+                             view,
+                             false)
+}
+
+pub fn image_for_texture(texture: &TextureTileInfo,
+                         pos: (i32, i32),
+                         view: (i32, i32),
+                         offset: (i32, i32),
+                         anim: Option<(u32, u32)>,
+                         dir: PlayerDir) -> Image {
+    let num_h_tiles = texture.0.get_width() / (texture.2).0;
+    let offset = ((texture.3).0 + offset.0, (texture.3).1 + offset.1);
+    let base = texture.4[dir as usize].unwrap_or(0);
+    let flip = dir == PlayerDir::Left && texture.4[PlayerDir::Left as usize] == texture.4[PlayerDir::Right as usize];
+    let anim_idx = anim.map_or(0, |a| a.0 / 150 % a.1);
+    image_for_tile_reference(num_h_tiles,
+                             texture.2.clone(),
+                             texture.1 + anim_idx,
+                             base,
+                             pos,
+                             offset,
+                             view,
+                             flip)
+}
+
+pub fn image_for_frame(texture: &TextureTileInfo,
+                       frame: u32,
+                       pos: (i32, i32),
+                       view: (i32, i32),
+                       offset: (i32, i32),
+                       flip: bool) -> Image {
+    let num_h_tiles = texture.0.get_width() / (texture.2).0;
+    let offset = ((texture.3).0 + offset.0, (texture.3).1 + offset.1);
+    image_for_tile_reference(num_h_tiles,
+                             texture.2.clone(),
+                             frame,
+                             0,
+                             pos,
+                             offset,
+                             view,
+                             flip)
+}
+
+pub fn image_for_tile_reference(num_h_tiles: u32,
+                                (tile_w, tile_h): (u32, u32),
+                                index: u32,
+                                index_y_offset: u32,
+                                (x, y): (i32, i32),
+                                (off_x, off_y): (i32, i32),
+                                (view_x, view_y): (i32, i32),
+                                flip_h: bool) -> Image {
+    let src_x = index % num_h_tiles * tile_w;
+    let src_y = (index / num_h_tiles + index_y_offset) * tile_h;
+    let src_rect = if flip_h {
+        [src_x as i32 + tile_w as i32,
+         src_y as i32,
+         -(tile_w as i32),
+         tile_h as i32]
+    } else {
+        [src_x as i32, src_y as i32, tile_w as i32, tile_h as i32]
+    };
+    Image::new()
+        .src_rect([src_rect[0] as f64, src_rect[1] as f64, src_rect[2] as f64, src_rect[3] as f64])
+        .rect([(x as i32 * 16) as f64 + off_x as f64 - view_x as f64,
+               (y as i32 * 16) as f64 + off_y as f64 - view_y as f64,
+               tile_w as f64,
+               tile_h as f64])
+}
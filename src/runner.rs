@@ -0,0 +1,495 @@
+use app::App;
+use character::{Actor, Character, Player, PlayerDir};
+use dialogue::{DialogueAction, DialogueBox, TextAboveHead};
+use event::{Command, End, ScriptedEvent};
+use font::Font;
+use graphics::{Context, Transformed};
+use opengl_graphics::GlGraphics;
+use piston::input::Key;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+const DEFAULT_SPEED: u8 = 2;
+const JUMP_DURATION: u32 = 400;
+const JUMP_HEIGHT: f64 = 16.;
+const WALK_FRAME_DURATION: u32 = 150;
+const TEXT_ABOVE_HEAD_DURATION: u32 = 3000;
+/// Where event scripts put characters that shouldn't be seen.
+const OFFSCREEN: (i32, i32) = (-100, -100);
+
+struct Movement {
+    actor: String,
+    remaining: (f64, f64),
+    facing: PlayerDir,
+}
+
+struct Jump {
+    actor: String,
+    start: u32,
+    last_height: f64,
+}
+
+struct Animation {
+    actor: String,
+    looping: bool,
+    interval: u32,
+    frames: Vec<u32>,
+    start: u32,
+}
+
+/// Steps through the commands of a `ScriptedEvent` over time, moving the
+/// event's characters and the camera as it goes.
+pub struct EventRunner {
+    elapsed: u32,
+    next_command: usize,
+    wait_until: u32,
+    speeds: HashMap<String, u8>,
+    movements: Vec<Movement>,
+    jumps: Vec<Jump>,
+    animations: Vec<Animation>,
+    dialogue: Option<DialogueBox>,
+    text_above_head: Vec<TextAboveHead>,
+    /// The answer to the current question that takes the next fork.
+    question_fork: Option<usize>,
+    /// Whether the next `fork` command should be taken.
+    fork_pending: bool,
+    /// The fork script currently being played, if not the main script.
+    current_fork: Option<String>,
+    ended: bool,
+    /// Whether the end clause sends the farmer out of the location.
+    warp_out: bool,
+    return_position: Option<(i32, i32)>,
+}
+
+fn actor_position(name: &str, characters: &[Character], player: &Player) -> Option<(f64, f64)> {
+    if name == "farmer" {
+        return Some(player.pixel_position());
+    }
+    characters.iter().find(|c| c.name == name).map(|c| c.pixel_position())
+}
+
+fn find_actor<'a>(name: &str,
+                  characters: &'a mut [Character],
+                  player: &'a mut Player) -> Option<&'a mut Actor> {
+    if name == "farmer" {
+        return Some(player as &mut Actor);
+    }
+    characters.iter_mut().find(|c| c.name == name).map(|c| c as &mut Actor)
+}
+
+impl EventRunner {
+    pub fn new() -> EventRunner {
+        EventRunner {
+            elapsed: 0,
+            next_command: 0,
+            wait_until: 0,
+            speeds: HashMap::new(),
+            movements: vec![],
+            jumps: vec![],
+            animations: vec![],
+            dialogue: None,
+            text_above_head: vec![],
+            question_fork: None,
+            fork_pending: false,
+            current_fork: None,
+            ended: false,
+            warp_out: false,
+            return_position: None,
+        }
+    }
+
+    /// The name of the fork script currently being played, if any.
+    pub fn current_fork(&self) -> Option<&str> {
+        self.current_fork.as_ref().map(|s| &s[..])
+    }
+
+    fn current_script<'a>(&self, event: &'a ScriptedEvent) -> &'a ScriptedEvent {
+        self.current_fork.as_ref()
+            .and_then(|name| event.find_fork(name))
+            .unwrap_or(event)
+    }
+
+    /// Whether the event is waiting on the keyboard, e.g. to page through
+    /// dialogue or answer a question.
+    pub fn wants_input(&self) -> bool {
+        self.dialogue.is_some()
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        let action = match self.dialogue {
+            Some(ref mut dialogue) => dialogue.key_pressed(key),
+            None => return,
+        };
+        match action {
+            DialogueAction::Continue => {}
+            DialogueAction::Finished => self.dialogue = None,
+            DialogueAction::Answered(answer) => {
+                self.fork_pending = self.question_fork == Some(answer);
+                self.dialogue = None;
+            }
+        }
+    }
+
+    /// Draw any dialogue and floating text over the map, where `view` is the
+    /// camera position in map pixels and `zoom` is the map's scale.
+    pub fn draw(&self,
+                font: &Font,
+                characters: &[Character],
+                player: &Player,
+                (view_x, view_y): (i32, i32),
+                zoom: f64,
+                c: Context,
+                gl: &mut GlGraphics) {
+        let transform = c.transform.zoom(zoom).trans(-view_x as f64, -view_y as f64);
+        for text in &self.text_above_head {
+            if let Some(pos) = actor_position(&text.actor, characters, player) {
+                text.draw(font, pos, transform, gl);
+            }
+        }
+
+        if let Some(ref dialogue) = self.dialogue {
+            dialogue.draw(font, c, gl);
+        }
+    }
+
+    /// Whether every command and the end clause have been run, and all
+    /// movement and dialogue has settled.
+    pub fn is_finished(&self) -> bool {
+        self.ended && self.is_idle()
+    }
+
+    /// Where to put the farmer when the event ends, unless the end clause
+    /// says otherwise.
+    pub fn set_return_position(&mut self, pos: (i32, i32)) {
+        self.return_position = Some(pos);
+    }
+
+    /// Whether the event has finished with an end clause that leaves the
+    /// location, such as `end warpOut`. Only returns true once.
+    pub fn take_warp_out(&mut self) -> bool {
+        if self.warp_out && self.is_finished() {
+            self.warp_out = false;
+            return true;
+        }
+        false
+    }
+
+    fn is_idle(&self) -> bool {
+        self.movements.is_empty() && self.dialogue.is_none() && self.elapsed >= self.wait_until
+    }
+
+    /// Advance the event by `dt` seconds.
+    pub fn update(&mut self,
+                  dt: f64,
+                  event: &ScriptedEvent,
+                  characters: &mut [Character],
+                  player: &mut Player,
+                  app: &mut App) {
+        self.elapsed += (dt * 1000.) as u32;
+
+        self.update_movements(dt, characters, player);
+        self.update_jumps(characters, player);
+        self.update_animations(characters);
+        let elapsed = self.elapsed;
+        self.text_above_head.retain(|t| t.expires > elapsed);
+
+        while self.is_idle() {
+            let script = self.current_script(event);
+            if self.next_command >= script.commands.len() {
+                break;
+            }
+            let command = &script.commands[self.next_command];
+            self.next_command += 1;
+            self.run_command(command, event, characters, player, app);
+        }
+
+        let script = self.current_script(event);
+        if !self.ended && self.is_idle() && self.next_command >= script.commands.len() {
+            self.ended = true;
+            self.run_end(&script.end, characters, player);
+        }
+
+        app.scripted = !self.is_finished();
+    }
+
+    fn run_end(&mut self, end: &End, characters: &mut [Character], player: &mut Player) {
+        for character in characters.iter_mut() {
+            character.frame = None;
+            character.flip = false;
+        }
+        self.animations.clear();
+
+        let hidden = match *end {
+            End::Invisible(ref npc) | End::InvisibleWarpOut(ref npc) => Some(npc),
+            _ => None,
+        };
+        if let Some(npc) = hidden {
+            if let Some(c) = characters.iter_mut().find(|c| c.name == *npc) {
+                c.set_position(OFFSCREEN, (0., 0.));
+            }
+        }
+
+        match *end {
+            End::Position((x, y)) => player.set_position((x as i32, y as i32), (0., 0.)),
+            End::Dialogue(ref npc, ref text) | End::DialogueWarpOut(ref npc, ref text) => {
+                self.dialogue = Some(DialogueBox::speech(npc, text));
+            }
+            _ => {}
+        }
+
+        self.warp_out = match *end {
+            End::WarpOut | End::DialogueWarpOut(..) | End::InvisibleWarpOut(..) => true,
+            _ => false,
+        };
+
+        // Only `end position` leaves the farmer where the event put them;
+        // everything else returns them to where they were before the event
+        // started. Warping out then takes them through the location's first
+        // warp, if the viewer follows it. There's no calendar to advance, so
+        // `end newDay` behaves like a bare `end`.
+        let stays = match *end {
+            End::Position(..) => true,
+            _ => false,
+        };
+        if let (false, Some(pos)) = (stays, self.return_position) {
+            player.set_position(pos, (0., 0.));
+        }
+    }
+
+    /// Switch to the fork script `name` if the last question's answer asked
+    /// for it. A fork that doesn't resolve is passed over, so the current
+    /// script carries on.
+    fn take_fork(&mut self, name: &str, event: &ScriptedEvent) {
+        if !self.fork_pending {
+            return;
+        }
+        self.fork_pending = false;
+        if event.find_fork(name).is_some() {
+            self.current_fork = Some(name.to_owned());
+            self.next_command = 0;
+        }
+    }
+
+    fn run_command(&mut self,
+                   command: &Command,
+                   event: &ScriptedEvent,
+                   characters: &mut [Character],
+                   player: &mut Player,
+                   app: &mut App) {
+        match *command {
+            Command::Pause(ms) => {
+                self.wait_until = self.elapsed + ms;
+            }
+            Command::Move(ref actor, (dx, dy), dir) => {
+                let facing = PlayerDir::from_event_dir(dir).unwrap_or(PlayerDir::Down);
+                if let Some(a) = find_actor(actor, characters, player) {
+                    a.set_dir(facing);
+                }
+                self.movements.push(Movement {
+                    actor: actor.clone(),
+                    remaining: ((dx * 16) as f64, (dy * 16) as f64),
+                    facing: facing,
+                });
+            }
+            Command::FaceDirection(ref actor, dir) => {
+                if let (Some(a), Some(dir)) = (find_actor(actor, characters, player),
+                                               PlayerDir::from_event_dir(dir)) {
+                    a.set_dir(dir);
+                }
+            }
+            Command::Warp(ref actor, pos) => {
+                if let Some(a) = find_actor(actor, characters, player) {
+                    a.set_position(pos, (0., 0.));
+                }
+            }
+            Command::Viewport(x, y) => {
+                app.view_x = x * 16;
+                app.view_y = y * 16;
+            }
+            Command::Speed(ref actor, speed) => {
+                self.speeds.insert(actor.clone(), speed);
+            }
+            Command::Jump(ref actor) => {
+                self.jumps.push(Jump {
+                    actor: actor.clone(),
+                    start: self.elapsed,
+                    last_height: 0.,
+                });
+            }
+            Command::PositionOffset(ref actor, dx, dy) => {
+                if let Some(a) = find_actor(actor, characters, player) {
+                    let (px, py) = a.pixel_position();
+                    a.set_pixel_position((px + dx as f64, py + dy as f64));
+                }
+            }
+            Command::ShowFrame(ref actor, frame) => {
+                self.animations.retain(|a| a.actor != *actor);
+                if let Some(c) = characters.iter_mut().find(|c| c.name == *actor) {
+                    c.frame = Some(frame);
+                    c.flip = false;
+                }
+            }
+            Command::Animate(ref actor, flip, looping, interval, ref frames) => {
+                self.animations.retain(|a| a.actor != *actor);
+                if let Some(c) = characters.iter_mut().find(|c| c.name == *actor) {
+                    c.flip = flip;
+                }
+                self.animations.push(Animation {
+                    actor: actor.clone(),
+                    looping: looping,
+                    interval: interval.max(1),
+                    frames: frames.clone(),
+                    start: self.elapsed,
+                });
+            }
+            Command::StopAnimation(ref actor) => {
+                self.animations.retain(|a| a.actor != *actor);
+                if let Some(c) = characters.iter_mut().find(|c| c.name == *actor) {
+                    c.frame = None;
+                    c.flip = false;
+                }
+            }
+            Command::Speak(ref actor, ref text) => {
+                self.dialogue = Some(DialogueBox::speech(actor, text));
+            }
+            Command::Message(ref text) => {
+                self.dialogue = Some(DialogueBox::message(text));
+            }
+            Command::Question(ref fork, ref text) => {
+                self.question_fork = if fork.starts_with("fork") {
+                    fork["fork".len()..].parse().ok()
+                } else {
+                    None
+                };
+                self.fork_pending = false;
+                self.dialogue = Some(DialogueBox::question(text));
+            }
+            Command::TextAboveHead(ref actor, ref text) => {
+                self.text_above_head.retain(|t| t.actor != *actor);
+                self.text_above_head.push(TextAboveHead {
+                    actor: actor.clone(),
+                    text: text.clone(),
+                    expires: self.elapsed + TEXT_ABOVE_HEAD_DURATION,
+                });
+            }
+            Command::Fork(ref name) => self.take_fork(name, event),
+            Command::Emote(..) |
+            Command::GlobalFade |
+            Command::PlaySound(..) |
+            Command::Shake(..) |
+            Command::AddQuest(..) |
+            Command::Mail(..) |
+            Command::Friendship(..) |
+            Command::PlayMusic(..) |
+            Command::SpecificTemporarySprite(..) |
+            Command::ChangeLocation(..) |
+            Command::ChangeToTemporaryMap(..) |
+            Command::AmbientLight(..) |
+            Command::Unknown(..) => (),
+        }
+    }
+
+    fn update_movements(&mut self, dt: f64, characters: &mut [Character], player: &mut Player) {
+        let elapsed = self.elapsed;
+        for movement in &mut self.movements {
+            let speed = *self.speeds.get(&movement.actor).unwrap_or(&DEFAULT_SPEED);
+            // Event speeds are measured in pixels per frame at 60fps.
+            let budget = speed as f64 * 60. * dt;
+            let (rx, ry) = movement.remaining;
+            let step_x = rx.abs().min(budget) * rx.signum();
+            let step_y = ry.abs().min(budget - step_x.abs()) * ry.signum();
+            movement.remaining = (rx - step_x, ry - step_y);
+
+            {
+                let actor = match find_actor(&movement.actor, characters, player) {
+                    Some(a) => a,
+                    None => {
+                        movement.remaining = (0., 0.);
+                        continue;
+                    }
+                };
+                let (px, py) = actor.pixel_position();
+                actor.set_pixel_position((px + step_x, py + step_y));
+                actor.set_dir(movement.facing);
+            }
+
+            if let Some(c) = characters.iter_mut().find(|c| c.name == movement.actor) {
+                let step = elapsed / WALK_FRAME_DURATION % 4;
+                c.frame = if movement.remaining == (0., 0.) {
+                    None
+                } else {
+                    Some(movement.facing as u32 * 4 + step)
+                };
+            }
+        }
+        self.movements.retain(|m| m.remaining != (0., 0.));
+    }
+
+    fn update_jumps(&mut self, characters: &mut [Character], player: &mut Player) {
+        let elapsed = self.elapsed;
+        for jump in &mut self.jumps {
+            let t = (elapsed - jump.start).min(JUMP_DURATION) as f64 / JUMP_DURATION as f64;
+            let height = (t * PI).sin() * JUMP_HEIGHT;
+            if let Some(a) = find_actor(&jump.actor, characters, player) {
+                let (px, py) = a.pixel_position();
+                a.set_pixel_position((px, py + jump.last_height - height));
+            }
+            jump.last_height = height;
+        }
+        self.jumps.retain(|j| elapsed - j.start < JUMP_DURATION);
+    }
+
+    fn update_animations(&mut self, characters: &mut [Character]) {
+        let elapsed = self.elapsed;
+        for animation in &self.animations {
+            if animation.frames.is_empty() {
+                continue;
+            }
+            let step = ((elapsed - animation.start) / animation.interval) as usize;
+            let step = if animation.looping {
+                step % animation.frames.len()
+            } else {
+                step.min(animation.frames.len() - 1)
+            };
+            if let Some(c) = characters.iter_mut().find(|c| c.name == animation.actor) {
+                c.frame = Some(animation.frames[step]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::{ParseMode, parse_event};
+
+    fn event(script: &str) -> ScriptedEvent {
+        let mut sources = HashMap::new();
+        sources.insert("100".to_owned(), script.to_owned());
+        parse_event("100", &sources, ParseMode::Lenient).unwrap()
+    }
+
+    #[test]
+    fn answered_forks_switch_scripts() {
+        let event = event("none/0 0/farmer 1 1 2/question fork0 \"Go?#Yes#No\"/fork Yes/end\nYes/pause 100/end");
+        let mut runner = EventRunner::new();
+        runner.next_command = 2;
+        runner.fork_pending = true;
+        runner.take_fork("Yes", &event);
+        assert_eq!(runner.current_fork(), Some("Yes"));
+        assert_eq!(runner.next_command, 0);
+    }
+
+    #[test]
+    fn missing_forks_are_passed_over() {
+        let event = event("none/0 0/farmer 1 1 2/question fork0 \"Go?#Yes#No\"/fork Nowhere/end");
+        assert!(event.forks.is_empty());
+        let mut runner = EventRunner::new();
+        runner.next_command = 2;
+        runner.fork_pending = true;
+        runner.take_fork("Nowhere", &event);
+        assert_eq!(runner.current_fork(), None);
+        assert_eq!(runner.next_command, 2);
+        assert!(!runner.fork_pending);
+    }
+}
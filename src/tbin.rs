@@ -0,0 +1,281 @@
+use map::{LayerProps, MapProps, SVMap, TileProps, TilesetProps};
+use std::io::{self, Read};
+use xnb::tide::{AnimatedTile, Layer, Map, PropertyParse, PropertyValue, StaticTile, Tile, TileSheet};
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads the primitives of the tIDE binary format.
+struct TbinReader<R> {
+    r: R,
+}
+
+impl<R: Read> TbinReader<R> {
+    fn u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.r.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        self.r.read_exact(&mut buf)?;
+        Ok(buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24)
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        self.u32().map(|v| v as i32)
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        self.u32().map(f32::from_bits)
+    }
+
+    fn bool(&mut self) -> io::Result<bool> {
+        self.u8().map(|b| b != 0)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Err(invalid(format!("negative string length {}", len)));
+        }
+        let mut buf = vec![0; len as usize];
+        self.r.read_exact(&mut buf)?;
+        String::from_utf8(buf).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn size(&mut self) -> io::Result<(u32, u32)> {
+        Ok((self.u32()?, self.u32()?))
+    }
+
+    fn properties(&mut self) -> io::Result<Vec<(String, PropertyValue)>> {
+        let count = self.i32()?;
+        let mut props = vec![];
+        for _ in 0..count {
+            let key = self.string()?;
+            let value = match self.u8()? {
+                0 => PropertyValue::Bool(self.bool()?),
+                1 => PropertyValue::Int(self.i32()?),
+                2 => PropertyValue::Float(self.f32()?),
+                3 => PropertyValue::String(self.string()?),
+                t => return Err(invalid(format!("unknown type {} for property {}", t, key))),
+            };
+            props.push((key, value));
+        }
+        Ok(props)
+    }
+
+    fn tilesheet(&mut self) -> io::Result<TileSheet<TilesetProps>> {
+        let id = self.string()?;
+        let description = self.string()?;
+        let image_source = self.string()?;
+        let sheet_size = self.size()?;
+        let tile_size = self.size()?;
+        let margin = self.size()?;
+        let spacing = self.size()?;
+        Ok(TileSheet {
+            id: id,
+            description: description,
+            image_source: image_source,
+            sheet_size: sheet_size,
+            tile_size: tile_size,
+            margin: margin,
+            spacing: spacing,
+            properties: TilesetProps::parse(self.properties()?),
+        })
+    }
+
+    fn static_tile(&mut self, sheet: &Option<String>, pos: (u32, u32)) -> io::Result<StaticTile<TileProps>> {
+        let tilesheet = match *sheet {
+            Some(ref sheet) => sheet.clone(),
+            None => return Err(invalid(format!("tile at {:?} has no tilesheet", pos))),
+        };
+        let index = self.u32()?;
+        let blend_mode = self.u8()?;
+        Ok(StaticTile {
+            pos: pos,
+            tilesheet: tilesheet,
+            index: index,
+            blend_mode: blend_mode,
+            properties: TileProps::parse(self.properties()?),
+        })
+    }
+
+    /// Read an animated tile. Its frames start on the layer's current
+    /// tilesheet, but switching tilesheets between frames doesn't affect the
+    /// tiles after it.
+    fn animated_tile(&mut self, sheet: &Option<String>, pos: (u32, u32)) -> io::Result<AnimatedTile<TileProps>> {
+        let frame_interval = self.u32()?;
+        let count = self.i32()?;
+        let mut frame_sheet = sheet.clone();
+        let mut frames = vec![];
+        while (frames.len() as i32) < count {
+            match self.u8()? {
+                b'T' => frame_sheet = Some(self.string()?),
+                b'S' => frames.push(self.static_tile(&frame_sheet, pos)?),
+                c => return Err(invalid(format!("unexpected {:?} in animated tile at {:?}", c as char, pos))),
+            }
+        }
+        Ok(AnimatedTile {
+            pos: pos,
+            frame_interval: frame_interval,
+            frames: frames,
+            properties: TileProps::parse(self.properties()?),
+        })
+    }
+
+    fn layer(&mut self) -> io::Result<Layer<LayerProps, TileProps>> {
+        let id = self.string()?;
+        let visible = self.bool()?;
+        let description = self.string()?;
+        let size = self.size()?;
+        let tile_size = self.size()?;
+        let properties = LayerProps::parse(self.properties()?);
+
+        // The current tilesheet carries over from one row to the next.
+        let mut tiles = vec![];
+        let mut sheet = None;
+        for y in 0..size.1 {
+            let mut x = 0;
+            while x < size.0 {
+                match self.u8()? {
+                    b'T' => sheet = Some(self.string()?),
+                    b'N' => {
+                        let skip = self.u32()?;
+                        x = x.checked_add(skip)
+                            .ok_or_else(|| invalid(format!("layer {} row {} skips {} tiles", id, y, skip)))?;
+                    }
+                    b'S' => {
+                        tiles.push(Tile::Static(self.static_tile(&sheet, (x, y))?));
+                        x += 1;
+                    }
+                    b'A' => {
+                        tiles.push(Tile::Animated(self.animated_tile(&sheet, (x, y))?));
+                        x += 1;
+                    }
+                    c => return Err(invalid(format!("unexpected {:?} in layer {} row {}", c as char, id, y))),
+                }
+            }
+        }
+
+        Ok(Layer {
+            id: id,
+            visible: visible,
+            description: description,
+            size: size,
+            tile_size: tile_size,
+            properties: properties,
+            tiles: tiles,
+        })
+    }
+}
+
+/// Read a map saved by tIDE in its binary `tBIN10` format.
+pub fn read_tbin<R: Read>(r: R) -> io::Result<SVMap> {
+    let mut r = TbinReader {
+        r: r,
+    };
+    let mut magic = [0; 6];
+    r.r.read_exact(&mut magic)?;
+    if &magic != b"tBIN10" {
+        return Err(invalid("not a tBIN10 file".to_owned()));
+    }
+
+    let id = r.string()?;
+    let description = r.string()?;
+    let properties = MapProps::parse(r.properties()?);
+
+    let mut tilesheets = vec![];
+    for _ in 0..r.i32()? {
+        tilesheets.push(r.tilesheet()?);
+    }
+    let mut layers = vec![];
+    for _ in 0..r.i32()? {
+        layers.push(r.layer()?);
+    }
+
+    Ok(Map {
+        id: id,
+        description: description,
+        properties: properties,
+        tilesheets: tilesheets,
+        layers: layers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds tBIN data by hand.
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn u8(&mut self, v: u8) -> &mut Writer {
+            self.0.push(v);
+            self
+        }
+
+        fn u32(&mut self, v: u32) -> &mut Writer {
+            self.0.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+            self
+        }
+
+        fn string(&mut self, s: &str) -> &mut Writer {
+            self.u32(s.len() as u32);
+            self.0.extend_from_slice(s.as_bytes());
+            self
+        }
+
+        fn tilesheet(&mut self, id: &str) -> &mut Writer {
+            self.string(id).string("").string(id);
+            self.u32(4).u32(4).u32(16).u32(16).u32(0).u32(0).u32(0).u32(0).u32(0)
+        }
+
+        fn static_tile(&mut self, index: u32) -> &mut Writer {
+            self.u8(b'S').u32(index).u8(0).u32(0)
+        }
+    }
+
+    #[test]
+    fn tilesheet_carries_across_rows() {
+        let mut w = Writer(b"tBIN10".to_vec());
+        w.string("Test").string("").u32(0);
+        w.u32(2).tilesheet("a").tilesheet("b");
+        w.u32(1).string("Back").u8(1).string("").u32(2).u32(2).u32(16).u32(16).u32(0);
+        // Row 0: a static tile from "a", then an animated tile whose frames
+        // switch to "b".
+        w.u8(b'T').string("a").static_tile(1);
+        w.u8(b'A').u32(250).u32(2).u8(b'T').string("b").static_tile(2).static_tile(3).u32(0);
+        // Row 1: no tilesheet switch, so this tile is still from "a".
+        w.static_tile(4).u8(b'N').u32(1);
+
+        let map = read_tbin(&w.0[..]).unwrap();
+        let tiles = &map.layers[0].tiles;
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[0].get_tilesheet(), "a");
+        assert_eq!(tiles[2].get_pos(), (0, 1));
+        assert_eq!(tiles[2].get_tilesheet(), "a");
+        assert_eq!(tiles[2].get_index(0), 4);
+    }
+
+    #[test]
+    fn tile_before_tilesheet_is_an_error() {
+        let mut w = Writer(b"tBIN10".to_vec());
+        w.string("Test").string("").u32(0).u32(0);
+        w.u32(1).string("Back").u8(1).string("").u32(1).u32(1).u32(16).u32(16).u32(0);
+        w.static_tile(0);
+        assert!(read_tbin(&w.0[..]).is_err());
+    }
+
+    #[test]
+    fn overlong_skip_is_an_error() {
+        let mut w = Writer(b"tBIN10".to_vec());
+        w.string("Test").string("").u32(0).u32(1).tilesheet("a");
+        w.u32(1).string("Back").u8(1).string("").u32(3).u32(1).u32(16).u32(16).u32(0);
+        w.u8(b'T').string("a").static_tile(0).u8(b'N').u32(u32::max_value());
+        assert!(read_tbin(&w.0[..]).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use assets::{AssetError, AssetSource};
+use image::{self, RgbaImage};
+use opengl_graphics::{Texture, TextureSettings, Filter};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use squish::{decompress_image, CompressType};
+use xnb::{XNB, SurfaceFormat, Texture2d};
+
+/// A texture along with the information needed to pick a frame out of it:
+/// base tile index, tile size, drawing offset, and per-direction row offsets.
+pub type TextureTileInfo = (Texture, u32, (u32, u32), (i32, i32), [Option<u32>; 4]);
+
+/// Why a texture couldn't be loaded.
+#[derive(Debug)]
+pub enum TextureError {
+    Missing(AssetError),
+    /// An unpacked image or XNB texture couldn't be decoded.
+    Decode(String),
+    /// The texture uses a surface format that can't be converted to RGBA.
+    UnsupportedFormat(String),
+    /// The pixel data is too short for the texture's format and size.
+    Truncated {
+        format: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureError::Missing(ref e) => write!(f, "{}", e),
+            TextureError::Decode(ref e) => write!(f, "couldn't decode image: {}", e),
+            TextureError::UnsupportedFormat(ref format) =>
+                write!(f, "unsupported texture format {}", format),
+            TextureError::Truncated { ref format, expected, actual } =>
+                write!(f, "{} texture needs {} bytes of data but has {}", format, expected, actual),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn description(&self) -> &str {
+        match *self {
+            TextureError::Missing(_) => "texture not found",
+            TextureError::Decode(_) => "couldn't decode image",
+            TextureError::UnsupportedFormat(_) => "unsupported texture format",
+            TextureError::Truncated { .. } => "truncated texture data",
+        }
+    }
+}
+
+impl From<AssetError> for TextureError {
+    fn from(e: AssetError) -> TextureError {
+        TextureError::Missing(e)
+    }
+}
+
+impl From<TextureError> for io::Error {
+    fn from(e: TextureError) -> io::Error {
+        match e {
+            TextureError::Missing(e) => e.into(),
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
+
+/// Expand a `bits`-wide color channel to 8 bits.
+fn expand(value: u16, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    (value as u32 * 255 / max) as u8
+}
+
+/// Convert 16-bit pixels to RGBA with `convert`.
+fn from_packed<F: Fn(u16) -> [u8; 4]>(data: &[u8], convert: F) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(data.len() * 2);
+    for pixel in data.chunks(2) {
+        let value = pixel[0] as u16 | (pixel[1] as u16) << 8;
+        rgba.extend_from_slice(&convert(value));
+    }
+    rgba
+}
+
+/// Decode the first mip level of an XNB texture into RGBA pixels.
+pub fn image_from_texture2d(texture: Texture2d) -> Result<RgbaImage, TextureError> {
+    let (width, height) = (texture.width as usize, texture.height as usize);
+    let pixels = width * height;
+    let blocks = ((width + 3) / 4) * ((height + 3) / 4);
+    let format = format!("{:?}", texture.format);
+    let data = match texture.mip_data.first() {
+        Some(data) => data,
+        None => return Err(TextureError::Decode(format!("{} texture has no mip levels", format))),
+    };
+
+    let expected = match texture.format {
+        SurfaceFormat::Color => pixels * 4,
+        SurfaceFormat::Bgr565 | SurfaceFormat::Bgra5551 | SurfaceFormat::Bgra4444 => pixels * 2,
+        SurfaceFormat::Alpha8 => pixels,
+        SurfaceFormat::Dxt1 => blocks * 8,
+        SurfaceFormat::Dxt3 | SurfaceFormat::Dxt5 => blocks * 16,
+        _ => return Err(TextureError::UnsupportedFormat(format)),
+    };
+    if data.len() < expected {
+        return Err(TextureError::Truncated {
+            format: format,
+            expected: expected,
+            actual: data.len(),
+        });
+    }
+
+    let decompress = |kind| decompress_image(width as i32, height as i32, data.as_ptr() as *const _, kind);
+    let rgba = match texture.format {
+        SurfaceFormat::Dxt1 => decompress(CompressType::Dxt1),
+        SurfaceFormat::Dxt3 => decompress(CompressType::Dxt3),
+        SurfaceFormat::Dxt5 => decompress(CompressType::Dxt5),
+        SurfaceFormat::Bgr565 => from_packed(&data[..expected], |v| {
+            [expand(v >> 11, 5), expand((v >> 5) & 0x3f, 6), expand(v & 0x1f, 5), 255]
+        }),
+        SurfaceFormat::Bgra5551 => from_packed(&data[..expected], |v| {
+            [expand((v >> 10) & 0x1f, 5), expand((v >> 5) & 0x1f, 5), expand(v & 0x1f, 5), expand(v >> 15, 1)]
+        }),
+        SurfaceFormat::Bgra4444 => from_packed(&data[..expected], |v| {
+            [expand((v >> 8) & 0xf, 4), expand((v >> 4) & 0xf, 4), expand(v & 0xf, 4), expand(v >> 12, 4)]
+        }),
+        SurfaceFormat::Alpha8 => {
+            let mut rgba = Vec::with_capacity(pixels * 4);
+            for &alpha in &data[..expected] {
+                rgba.extend_from_slice(&[255, 255, 255, alpha]);
+            }
+            rgba
+        }
+        _ => data[..expected].to_vec(),
+    };
+    Ok(RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .expect("decoded texture has the wrong size"))
+}
+
+pub fn texture_from_image(img: &RgbaImage) -> Texture {
+    let mut settings = TextureSettings::new();
+    settings.set_filter(Filter::Nearest);
+    Texture::from_image(img, &settings)
+}
+
+fn load_xnb_image(assets: &AssetSource, filename: &Path) -> Result<RgbaImage, TextureError> {
+    let mut f = assets.open(filename)?;
+    let xnb = XNB::<Texture2d>::from_buffer(&mut f)
+        .map_err(|e| TextureError::Decode(format!("{}: {:?}", filename.display(), e)))?;
+    image_from_texture2d(xnb.primary)
+}
+
+fn load_png_image(assets: &AssetSource, filename: &Path) -> Result<RgbaImage, TextureError> {
+    let path = assets.find(filename)?;
+    let img = image::open(&path).map_err(|e| TextureError::Decode(format!("{}: {}", path.display(), e)))?;
+    Ok(img.to_rgba())
+}
+
+/// Load the texture `filename`, or an unpacked `.png` with the same name
+/// instead. The `.png` is used if the texture is missing, or whenever it
+/// exists if the asset source prefers unpacked images.
+pub fn load_image(assets: &AssetSource, filename: &str) -> Result<RgbaImage, TextureError> {
+    let xnb = Path::new(filename);
+    let png = xnb.with_extension("png");
+    let (first, second): (&Path, &Path) = if assets.prefer_png {
+        (&png, xnb)
+    } else {
+        (xnb, &png)
+    };
+    let load = |path: &Path| if path == png.as_path() {
+        load_png_image(assets, path)
+    } else {
+        load_xnb_image(assets, path)
+    };
+
+    match load(first) {
+        Err(TextureError::Missing(first_err)) => match load(second) {
+            Err(TextureError::Missing(second_err)) => {
+                let mut searched = first_err.searched;
+                searched.extend(second_err.searched);
+                Err(TextureError::Missing(AssetError {
+                    path: xnb.to_owned(),
+                    searched: searched,
+                }))
+            }
+            result => result,
+        },
+        result => result,
+    }
+}
+
+pub fn load_texture(assets: &AssetSource, filename: &str) -> Result<Texture, TextureError> {
+    load_image(assets, filename).map(|img| texture_from_image(&img))
+}
@@ -0,0 +1,575 @@
+use assets::AssetSource;
+use base64;
+use map::{LayerProps, MapProps, SVMap, TileProps, TilesetProps, tile_props};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use texture::load_image;
+use xml::reader::{EventReader, XmlEvent};
+use xnb::tide::{AnimatedTile, Layer, Map, PropertyParse, PropertyValue, StaticTile, Tile, TileSheet};
+
+/// The bits of a global tile id that hold its flip flags.
+const FLIP_FLAGS: u32 = 0xe0000000;
+
+/// The name of the objects that hold the properties of the map tile they
+/// cover, in an object group named after the tile's layer.
+const TILE_DATA: &'static str = "TileData";
+
+/// Properties read from `TileData` objects, by tile position.
+type TileData = HashMap<(u32, u32), Vec<(String, PropertyValue)>>;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// An XML element with its attributes, children and text content.
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|s| &s[..])
+    }
+
+    fn required(&self, name: &str) -> io::Result<&str> {
+        self.attr(name).ok_or_else(|| invalid(format!("<{}> is missing {}", self.name, name)))
+    }
+
+    fn number(&self, name: &str) -> io::Result<u32> {
+        let value = self.required(name)?;
+        value.parse().map_err(|_| invalid(format!("<{}> has invalid {} {:?}", self.name, name, value)))
+    }
+
+    /// A size attribute, which has to be at least 1.
+    fn dimension(&self, name: &str) -> io::Result<u32> {
+        match self.number(name)? {
+            0 => Err(invalid(format!("<{}> has zero {}", self.name, name))),
+            value => Ok(value),
+        }
+    }
+
+    fn coordinate(&self, name: &str) -> io::Result<f64> {
+        let value = self.required(name)?;
+        value.parse().map_err(|_| invalid(format!("<{}> has invalid {} {:?}", self.name, name, value)))
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> Box<Iterator<Item = &'a Element> + 'a> {
+        Box::new(self.children.iter().filter(move |c| c.name == name))
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+}
+
+fn parse_document<R: Read>(r: R) -> io::Result<Element> {
+    let mut stack: Vec<Element> = vec![];
+    for event in EventReader::new(r) {
+        match event.map_err(|e| invalid(e.to_string()))? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                stack.push(Element {
+                    name: name.local_name,
+                    attributes: attributes.into_iter().map(|a| (a.name.local_name, a.value)).collect(),
+                    children: vec![],
+                    text: String::new(),
+                });
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().expect("unbalanced XML");
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(invalid("no root element".to_owned()))
+}
+
+fn properties(element: &Element) -> Vec<(String, PropertyValue)> {
+    let props = match element.child("properties") {
+        Some(props) => props,
+        None => return vec![],
+    };
+    props.children("property").filter_map(|p| {
+        let name = p.attr("name")?.to_owned();
+        let value = p.attr("value").map(|v| v.to_owned()).unwrap_or_else(|| p.text.clone());
+        let value = match p.attr("type") {
+            Some("bool") => PropertyValue::Bool(value == "true"),
+            Some("int") => PropertyValue::Int(value.parse().unwrap_or(0)),
+            Some("float") => PropertyValue::Float(value.parse().unwrap_or(0.)),
+            _ => PropertyValue::String(value),
+        };
+        Some((name, value))
+    }).collect()
+}
+
+/// A tileset along with what's needed to map global tile ids into it.
+struct Tileset {
+    first_gid: u32,
+    id: String,
+    /// Frame indices and the frame interval for each animated tile.
+    animations: HashMap<u32, (Vec<u32>, u32)>,
+}
+
+fn read_tileset(element: &Element) -> io::Result<(Tileset, TileSheet<TilesetProps>)> {
+    if element.attr("source").is_some() {
+        return Err(invalid("external tilesets (.tsx) aren't supported; embed them in the map".to_owned()));
+    }
+    let id = element.required("name")?.to_owned();
+    let tile_size = (element.dimension("tilewidth")?, element.dimension("tileheight")?);
+    let image = element.child("image").ok_or_else(|| invalid(format!("tileset {} has no image", id)))?;
+    let sheet_size = match element.attr("columns") {
+        Some(_) => {
+            let columns = element.number("columns")?.max(1);
+            (columns, element.number("tilecount")? / columns)
+        }
+        None => (image.number("width")? / tile_size.0, image.number("height")? / tile_size.1),
+    };
+    let margin = element.attr("margin").and_then(|m| m.parse().ok()).unwrap_or(0);
+    let spacing = element.attr("spacing").and_then(|s| s.parse().ok()).unwrap_or(0);
+
+    // The map's tilesheets are loaded relative to the asset root, like the
+    // ones named in XNB maps.
+    let source = Path::new(image.required("source")?);
+    let image_source = Path::new("Maps")
+        .join(source.with_extension(""))
+        .to_string_lossy()
+        .into_owned();
+
+    let mut props = properties(element);
+    let mut animations = HashMap::new();
+    for tile in element.children("tile") {
+        let index = tile.number("id")?;
+        for (name, value) in properties(tile) {
+            props.push((format!("@TileIndex@{}@{}", index, name), value));
+        }
+        if let Some(animation) = tile.child("animation") {
+            let frames: Vec<_> = animation.children("frame").collect();
+            let indices = frames.iter().map(|f| f.number("tileid")).collect::<io::Result<Vec<_>>>()?;
+            let interval = match frames.first() {
+                Some(frame) => frame.number("duration")?,
+                None => continue,
+            };
+            animations.insert(index, (indices, interval));
+        }
+    }
+
+    let tileset = Tileset {
+        first_gid: element.number("firstgid")?,
+        id: id.clone(),
+        animations: animations,
+    };
+    let sheet = TileSheet {
+        id: id,
+        description: String::new(),
+        image_source: image_source,
+        sheet_size: sheet_size,
+        tile_size: tile_size,
+        margin: (margin, margin),
+        spacing: (spacing, spacing),
+        properties: TilesetProps::parse(props),
+    };
+    Ok((tileset, sheet))
+}
+
+/// The global tile ids stored in a layer's `<data>`.
+fn read_gids(data: &Element) -> io::Result<Vec<u32>> {
+    if data.attr("compression").is_some() {
+        return Err(invalid("compressed layer data isn't supported; save with CSV or uncompressed base64".to_owned()));
+    }
+    match data.attr("encoding") {
+        Some("csv") => {
+            data.text
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().map_err(|_| invalid(format!("invalid tile id {:?}", s))))
+                .collect()
+        }
+        Some("base64") => {
+            let bytes = base64::decode(data.text.trim()).map_err(|e| invalid(e.to_string()))?;
+            Ok(bytes.chunks(4)
+                .filter(|b| b.len() == 4)
+                .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+                .collect())
+        }
+        Some(encoding) => Err(invalid(format!("unknown layer encoding {}", encoding))),
+        None => data.children("tile").map(|t| Ok(t.attr("gid").and_then(|g| g.parse().ok()).unwrap_or(0))).collect(),
+    }
+}
+
+/// Read the `TileData` objects of every object group, keyed by the name of
+/// the group.
+fn read_tile_data(root: &Element, tile_size: (u32, u32)) -> io::Result<HashMap<String, TileData>> {
+    let mut layers = HashMap::new();
+    for group in root.children("objectgroup") {
+        let data = layers.entry(group.attr("name").unwrap_or("").to_owned()).or_insert_with(HashMap::new);
+        for object in group.children("object").filter(|o| o.attr("name") == Some(TILE_DATA)) {
+            let x = object.coordinate("x")? / tile_size.0 as f64;
+            let y = object.coordinate("y")? / tile_size.1 as f64;
+            if x < 0. || y < 0. {
+                continue;
+            }
+            data.insert((x as u32, y as u32), properties(object));
+        }
+    }
+    Ok(layers)
+}
+
+fn read_layer(element: &Element,
+              tilesets: &[Tileset],
+              tile_size: (u32, u32),
+              tile_data: &mut TileData) -> io::Result<Layer<LayerProps, TileProps>> {
+    let id = element.required("name")?.to_owned();
+    let size = (element.dimension("width")?, element.dimension("height")?);
+    let data = element.child("data").ok_or_else(|| invalid(format!("layer {} has no data", id)))?;
+    if data.child("chunk").is_some() {
+        return Err(invalid("infinite maps aren't supported".to_owned()));
+    }
+    let gids = read_gids(data)?;
+
+    let mut tiles = vec![];
+    for (i, &gid) in gids.iter().enumerate() {
+        let gid = gid & !FLIP_FLAGS;
+        if gid == 0 {
+            continue;
+        }
+        let pos = (i as u32 % size.0, i as u32 / size.0);
+        let tileset = tilesets.iter()
+            .filter(|t| t.first_gid <= gid)
+            .max_by_key(|t| t.first_gid)
+            .ok_or_else(|| invalid(format!("tile id {} at {:?} has no tileset", gid, pos)))?;
+        let index = gid - tileset.first_gid;
+        let static_tile = |index| StaticTile {
+            pos: pos,
+            tilesheet: tileset.id.clone(),
+            index: index,
+            blend_mode: 0,
+            properties: TileProps::parse(vec![]),
+        };
+        let properties = TileProps::parse(tile_data.remove(&pos).unwrap_or_default());
+        tiles.push(match tileset.animations.get(&index) {
+            Some(&(ref frames, interval)) => Tile::Animated(AnimatedTile {
+                pos: pos,
+                frame_interval: interval,
+                frames: frames.iter().map(|&f| static_tile(f)).collect(),
+                properties: properties,
+            }),
+            None => {
+                let mut tile = static_tile(index);
+                tile.properties = properties;
+                Tile::Static(tile)
+            }
+        });
+    }
+
+    Ok(Layer {
+        id: id,
+        visible: element.attr("visible") != Some("0"),
+        description: String::new(),
+        size: size,
+        tile_size: tile_size,
+        properties: LayerProps::parse(properties(element)),
+        tiles: tiles,
+    })
+}
+
+/// Read a map saved by Tiled. Tile properties on tilesets become the
+/// `@TileIndex@<index>@<name>` tilesheet properties that tIDE uses, and the
+/// properties of `TileData` objects become those of the map tile under them.
+pub fn read_tmx<R: Read>(r: R) -> io::Result<SVMap> {
+    let root = parse_document(r)?;
+    if root.name != "map" {
+        return Err(invalid(format!("expected <map>, found <{}>", root.name)));
+    }
+    let tile_size = (root.dimension("tilewidth")?, root.dimension("tileheight")?);
+
+    let mut tilesets = vec![];
+    let mut tilesheets = vec![];
+    for element in root.children("tileset") {
+        let (tileset, sheet) = read_tileset(element)?;
+        tilesets.push(tileset);
+        tilesheets.push(sheet);
+    }
+
+    let mut tile_data = read_tile_data(&root, tile_size)?;
+    let layers = root.children("layer")
+        .map(|l| {
+            let mut data = tile_data.remove(l.attr("name").unwrap_or("")).unwrap_or_default();
+            read_layer(l, &tilesets, tile_size, &mut data)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(Map {
+        id: String::new(),
+        description: String::new(),
+        properties: MapProps::parse(properties(&root)),
+        tilesheets: tilesheets,
+        layers: layers,
+    })
+}
+
+/// Escape `s` for use in an XML attribute.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
+/// Write a `<properties>` element for `props`, if there are any.
+fn write_properties<W: Write>(w: &mut W, indent: &str, props: &[(String, String)]) -> io::Result<()> {
+    if props.is_empty() {
+        return Ok(());
+    }
+    writeln!(w, "{}<properties>", indent)?;
+    for &(ref name, ref value) in props {
+        writeln!(w, r#"{} <property name="{}" value="{}"/>"#, indent, escape(name), escape(value))?;
+    }
+    writeln!(w, "{}</properties>", indent)
+}
+
+/// The per-tile properties of a tilesheet, in the form tIDE stores them.
+fn tile_properties(props: &TilesetProps) -> BTreeMap<u32, Vec<(String, String)>> {
+    props.indices().into_iter().map(|index| (index, props.tile_properties(index))).collect()
+}
+
+/// The file name of the PNG that a tilesheet's image is extracted to.
+pub fn tilesheet_png(image_source: &str) -> String {
+    let name = image_source.rsplit(|c| c == '/' || c == '\\').next().unwrap_or(image_source);
+    format!("{}.png", name)
+}
+
+/// Write `map` as a Tiled map with embedded tilesets whose images are the
+/// PNGs named by `tilesheet_png`. The properties of map tiles are written
+/// to `TileData` objects in an object group named after each layer.
+pub fn write_tmx<W: Write>(map: &SVMap, w: &mut W) -> io::Result<()> {
+    let size = map.layers.first().map_or((0, 0), |l| l.size);
+    let tile_size = map.tilesheets.first().map_or((16, 16), |t| t.tile_size);
+
+    // Tiled animates tiles in the tileset rather than on the map, so collect
+    // the animation of the first animated tile found for each tile index.
+    let mut animations: HashMap<(&str, u32), (Vec<u32>, u32)> = HashMap::new();
+    for layer in &map.layers {
+        for tile in &layer.tiles {
+            if let Tile::Animated(ref animated) = *tile {
+                if let Some(first) = animated.frames.first() {
+                    animations.entry((&first.tilesheet[..], first.index)).or_insert_with(|| {
+                        (animated.frames.iter().map(|f| f.index).collect(), animated.frame_interval)
+                    });
+                }
+            }
+        }
+    }
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w,
+             r#"<map version="1.0" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{}" tileheight="{}">"#,
+             size.0, size.1, tile_size.0, tile_size.1)?;
+    write_properties(w, " ", &map.properties.properties())?;
+
+    let mut first_gids = HashMap::new();
+    let mut next_gid = 1;
+    for sheet in &map.tilesheets {
+        let count = sheet.sheet_size.0 * sheet.sheet_size.1;
+        first_gids.insert(&sheet.id[..], next_gid);
+        writeln!(w,
+                 r#" <tileset firstgid="{}" name="{}" tilewidth="{}" tileheight="{}" tilecount="{}" columns="{}">"#,
+                 next_gid, escape(&sheet.id), sheet.tile_size.0, sheet.tile_size.1, count, sheet.sheet_size.0)?;
+        writeln!(w,
+                 r#"  <image source="{}" width="{}" height="{}"/>"#,
+                 escape(&tilesheet_png(&sheet.image_source)),
+                 sheet.sheet_size.0 * sheet.tile_size.0,
+                 sheet.sheet_size.1 * sheet.tile_size.1)?;
+
+        let mut tiles = tile_properties(&sheet.properties);
+        for &(sheet_id, index) in animations.keys() {
+            if sheet_id == sheet.id {
+                tiles.entry(index).or_insert_with(Vec::new);
+            }
+        }
+        for (index, props) in &tiles {
+            writeln!(w, r#"  <tile id="{}">"#, index)?;
+            write_properties(w, "   ", props)?;
+            if let Some(&(ref frames, interval)) = animations.get(&(&sheet.id[..], *index)) {
+                writeln!(w, "   <animation>")?;
+                for frame in frames {
+                    writeln!(w, r#"    <frame tileid="{}" duration="{}"/>"#, frame, interval)?;
+                }
+                writeln!(w, "   </animation>")?;
+            }
+            writeln!(w, "  </tile>")?;
+        }
+        writeln!(w, " </tileset>")?;
+        next_gid += count;
+    }
+
+    for layer in &map.layers {
+        let mut gids = vec![0; (layer.size.0 * layer.size.1) as usize];
+        for tile in &layer.tiles {
+            let (x, y) = tile.get_pos();
+            if x >= layer.size.0 || y >= layer.size.1 {
+                continue;
+            }
+            if let Some(&first_gid) = first_gids.get(tile.get_tilesheet()) {
+                gids[(y * layer.size.0 + x) as usize] = first_gid + tile.get_index(0);
+            }
+        }
+
+        writeln!(w,
+                 r#" <layer name="{}" width="{}" height="{}"{}>"#,
+                 escape(&layer.id), layer.size.0, layer.size.1,
+                 if layer.visible { "" } else { r#" visible="0""# })?;
+        writeln!(w, r#"  <data encoding="csv">"#)?;
+        let rows: Vec<_> = gids.chunks(layer.size.0.max(1) as usize).map(|row| {
+            row.iter().map(|g| g.to_string()).collect::<Vec<_>>().join(",")
+        }).collect();
+        writeln!(w, "{}", rows.join(",\n"))?;
+        writeln!(w, "  </data>")?;
+        writeln!(w, " </layer>")?;
+    }
+
+    let mut next_object_id = 1;
+    for layer in &map.layers {
+        let tiles: Vec<_> = layer.tiles.iter().filter_map(|tile| {
+            let props = tile_props(tile).properties();
+            if props.is_empty() {
+                None
+            } else {
+                Some((tile.get_pos(), props))
+            }
+        }).collect();
+        if tiles.is_empty() {
+            continue;
+        }
+        writeln!(w, r#" <objectgroup name="{}">"#, escape(&layer.id))?;
+        for ((x, y), props) in tiles {
+            writeln!(w,
+                     r#"  <object id="{}" name="{}" x="{}" y="{}" width="{}" height="{}">"#,
+                     next_object_id, TILE_DATA,
+                     x * tile_size.0, y * tile_size.1, tile_size.0, tile_size.1)?;
+            write_properties(w, "   ", &props)?;
+            writeln!(w, "  </object>")?;
+            next_object_id += 1;
+        }
+        writeln!(w, " </objectgroup>")?;
+    }
+    writeln!(w, "</map>")
+}
+
+/// Write `map` to `<dir>/<name>.tmx` along with its tilesheets extracted
+/// to PNGs, returning the path of the TMX file.
+pub fn export_tmx(map: &SVMap, assets: &AssetSource, name: &str, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    for sheet in &map.tilesheets {
+        let image = load_image(assets, &format!("{}.xnb", sheet.image_source))?;
+        image.save(dir.join(tilesheet_png(&sheet.image_source)))?;
+    }
+
+    let path = dir.join(Path::new(name).with_extension("tmx"));
+    let mut f = BufWriter::new(File::create(&path)?);
+    write_tmx(map, &mut f)?;
+    f.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16">
+ <properties>
+  <property name="Warp" value="0 1 Town 10 20 2 1 Farm 30 40"/>
+  <property name="Music" value="springtown"/>
+  <property name="Outdoors" value="T"/>
+  <property name="Custom" value="kept"/>
+ </properties>
+ <tileset firstgid="1" name="outdoors" tilewidth="16" tileheight="16" tilecount="8" columns="4">
+  <image source="spring_outdoorsTileSheet.png" width="64" height="32"/>
+  <tile id="2">
+   <properties>
+    <property name="Passable" value="T"/>
+   </properties>
+   <animation>
+    <frame tileid="2" duration="250"/>
+    <frame tileid="3" duration="250"/>
+   </animation>
+  </tile>
+ </tileset>
+ <layer name="Buildings" width="3" height="2">
+  <data encoding="csv">
+1,0,3,
+0,5,0
+  </data>
+ </layer>
+ <objectgroup name="Buildings">
+  <object id="1" name="TileData" x="16" y="16" width="16" height="16">
+   <properties>
+    <property name="Action" value="Warp 6 29 Town"/>
+    <property name="TouchAction" value="Door"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    fn check(map: &SVMap) {
+        let props = &map.properties;
+        assert_eq!(props.warps.len(), 2);
+        assert_eq!(props.warps[1].map, "Farm");
+        assert_eq!(props.warps[1].to, (30, 40));
+        assert_eq!(props.music.as_ref().map(|s| &s[..]), Some("springtown"));
+        assert!(props.outdoors);
+        assert_eq!(props.other, [("Custom".to_owned(), "kept".to_owned())]);
+
+        let sheet = &map.tilesheets[0];
+        assert_eq!(sheet.properties.tile_is_passable(2), Some(true));
+
+        let tiles = &map.layers[0].tiles;
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles.iter().map(|t| t.get_pos()).collect::<Vec<_>>(), [(0, 0), (2, 0), (1, 1)]);
+        match tiles[1] {
+            Tile::Animated(ref t) => assert_eq!(t.frames.iter().map(|f| f.index).collect::<Vec<_>>(), [2, 3]),
+            Tile::Static(_) => panic!("expected an animated tile"),
+        }
+        let door = tile_props(&tiles[2]);
+        assert_eq!(door.action.as_ref().map(|s| &s[..]), Some("Warp 6 29 Town"));
+        assert_eq!(door.other, [("TouchAction".to_owned(), "Door".to_owned())]);
+        assert!(tile_props(&tiles[0]).properties().is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let map = read_tmx(MAP.as_bytes()).unwrap();
+        check(&map);
+
+        let mut written = vec![];
+        write_tmx(&map, &mut written).unwrap();
+        check(&read_tmx(&written[..]).unwrap());
+    }
+
+    #[test]
+    fn zero_sizes_are_rejected() {
+        for &(from, to) in &[(r#"<layer name="Buildings" width="3""#, r#"<layer name="Buildings" width="0""#),
+                             (r#"name="outdoors" tilewidth="16""#, r#"name="outdoors" tilewidth="0""#),
+                             (r#"height="2" tilewidth="16""#, r#"height="2" tilewidth="0""#)] {
+            let map = MAP.replace(from, to);
+            assert!(map != MAP);
+            assert!(read_tmx(map.as_bytes()).is_err());
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use event::{event_keys, parse_triggers};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl FromStr for Weekday {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Weekday, ()> {
+        Ok(match s {
+            "Mon" => Weekday::Mon,
+            "Tue" => Weekday::Tue,
+            "Wed" => Weekday::Wed,
+            "Thu" => Weekday::Thu,
+            "Fri" => Weekday::Fri,
+            "Sat" => Weekday::Sat,
+            "Sun" => Weekday::Sun,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+    Winter,
+}
+
+impl FromStr for Season {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Season, ()> {
+        Ok(match s {
+            "spring" => Season::Spring,
+            "summer" => Season::Summer,
+            "fall" => Season::Fall,
+            "winter" => Season::Winter,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Weather {
+    Sunny,
+    Rainy,
+}
+
+impl FromStr for Weather {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Weather, ()> {
+        Ok(match s {
+            "sunny" => Weather::Sunny,
+            "rainy" => Weather::Rainy,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// A precondition from an event key, such as `f Abigail 1000` or `t 600 1200`.
+pub enum Trigger {
+    /// `d`: not on any of these days of the week.
+    NotDayOfWeek(Vec<Weekday>),
+    /// `z`: not in any of these seasons.
+    NotSeason(Vec<Season>),
+    /// `u`: only on one of these days of the month.
+    DayOfMonth(Vec<u32>),
+    /// `y`: in the first year if 1, otherwise in this year or later.
+    Year(u32),
+    /// `t`: between these times of day.
+    TimeRange(u32, u32),
+    /// `w`: only in this weather.
+    Weather(Weather),
+    /// `f`: at least this many friendship points with each NPC.
+    Friendship(Vec<(String, u32)>),
+    /// `e`: has seen all of these events.
+    SeenEvents(Vec<String>),
+    /// `k`: has seen none of these events.
+    NotSeenEvents(Vec<String>),
+    /// `n`: has received this mail.
+    Mail(String),
+    /// `l`: has not received this mail.
+    NotMail(String),
+    /// `j`: has played more than this many days.
+    DaysPlayed(u32),
+    /// `m`: has earned at least this much money.
+    MoneyEarned(u32),
+    /// `O`: is married to this NPC.
+    MarriedTo(String),
+    /// `D`: is dating this NPC.
+    Dating(String),
+    /// `p`: this NPC is in the current location.
+    NpcPresent(String),
+    /// `r`: fires with this probability.
+    Random(f64),
+    /// Any precondition that isn't understood.
+    Unknown(Vec<String>),
+}
+
+/// The parts of a save game that event preconditions depend on.
+pub struct GameState {
+    pub day_of_week: Weekday,
+    pub day_of_month: u32,
+    pub season: Season,
+    pub year: u32,
+    pub time: u32,
+    pub weather: Weather,
+    pub friendship: HashMap<String, u32>,
+    pub seen_events: HashSet<String>,
+    pub mail: HashSet<String>,
+    pub days_played: u32,
+    pub money_earned: u32,
+    pub spouse: Option<String>,
+    pub dating: HashSet<String>,
+    pub present_npcs: HashSet<String>,
+}
+
+impl Default for GameState {
+    fn default() -> GameState {
+        GameState {
+            day_of_week: Weekday::Mon,
+            day_of_month: 1,
+            season: Season::Spring,
+            year: 1,
+            time: 600,
+            weather: Weather::Sunny,
+            friendship: HashMap::new(),
+            seen_events: HashSet::new(),
+            mail: HashSet::new(),
+            days_played: 1,
+            money_earned: 0,
+            spouse: None,
+            dating: HashSet::new(),
+            present_npcs: HashSet::new(),
+        }
+    }
+}
+
+impl GameState {
+    /// Update the state from a `key=value` setting such as `season=fall` or
+    /// `friendship=Abigail:1000`.
+    pub fn set(&mut self, setting: &str) -> Result<(), String> {
+        let mut parts = setting.splitn(2, '=');
+        let key = parts.next().unwrap_or("");
+        let value = match parts.next() {
+            Some(value) => value,
+            None => return Err(format!("expected key=value, got {:?}", setting)),
+        };
+        let invalid = || format!("invalid value for {}: {:?}", key, value);
+        match key {
+            "weekday" => self.day_of_week = value.parse().map_err(|_| invalid())?,
+            "day" => self.day_of_month = value.parse().map_err(|_| invalid())?,
+            "season" => self.season = value.parse().map_err(|_| invalid())?,
+            "year" => self.year = value.parse().map_err(|_| invalid())?,
+            "time" => self.time = value.parse().map_err(|_| invalid())?,
+            "weather" => self.weather = value.parse().map_err(|_| invalid())?,
+            "daysPlayed" => self.days_played = value.parse().map_err(|_| invalid())?,
+            "money" => self.money_earned = value.parse().map_err(|_| invalid())?,
+            "spouse" => self.spouse = Some(value.to_owned()),
+            "dating" => { self.dating.insert(value.to_owned()); }
+            "present" => { self.present_npcs.insert(value.to_owned()); }
+            "seen" => { self.seen_events.insert(value.to_owned()); }
+            "mail" => { self.mail.insert(value.to_owned()); }
+            "friendship" => {
+                let mut parts = value.splitn(2, ':');
+                let npc = parts.next().unwrap_or("");
+                let points = parts.next().and_then(|p| p.parse().ok()).ok_or_else(|| invalid())?;
+                self.friendship.insert(npc.to_owned(), points);
+            }
+            _ => return Err(format!("unknown game state key {:?}", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Trigger {
+    /// Whether this precondition holds in `state`. Preconditions that aren't
+    /// understood never hold; random ones hold if they have any chance.
+    pub fn is_satisfied(&self, state: &GameState) -> bool {
+        match *self {
+            Trigger::NotDayOfWeek(ref days) => !days.contains(&state.day_of_week),
+            Trigger::NotSeason(ref seasons) => !seasons.contains(&state.season),
+            Trigger::DayOfMonth(ref days) => days.contains(&state.day_of_month),
+            Trigger::Year(1) => state.year == 1,
+            Trigger::Year(year) => state.year >= year,
+            Trigger::TimeRange(start, end) => state.time >= start && state.time <= end,
+            Trigger::Weather(weather) => state.weather == weather,
+            Trigger::Friendship(ref npcs) => npcs.iter().all(|&(ref npc, points)| {
+                state.friendship.get(npc).map_or(false, |&p| p >= points)
+            }),
+            Trigger::SeenEvents(ref ids) => ids.iter().all(|id| state.seen_events.contains(id)),
+            Trigger::NotSeenEvents(ref ids) => !ids.iter().any(|id| state.seen_events.contains(id)),
+            Trigger::Mail(ref mail) => state.mail.contains(mail),
+            Trigger::NotMail(ref mail) => !state.mail.contains(mail),
+            Trigger::DaysPlayed(days) => state.days_played > days,
+            Trigger::MoneyEarned(money) => state.money_earned >= money,
+            Trigger::MarriedTo(ref npc) => state.spouse.as_ref() == Some(npc),
+            Trigger::Dating(ref npc) => state.dating.contains(npc),
+            Trigger::NpcPresent(ref npc) => state.present_npcs.contains(npc),
+            Trigger::Random(chance) => chance > 0.,
+            Trigger::Unknown(_) => false,
+        }
+    }
+}
+
+/// The ids of the events in an events dictionary whose preconditions all hold
+/// in `state`, in sorted order. Events with unparseable preconditions and
+/// fork scripts are skipped.
+pub fn firing_events(sources: &HashMap<String, String>, state: &GameState) -> Vec<String> {
+    let mut ids: Vec<_> = event_keys(sources).into_iter().filter_map(|key| {
+        let triggers = match parse_triggers(&key) {
+            Ok(triggers) => triggers,
+            Err(_) => return None,
+        };
+        if triggers.iter().all(|t| t.is_satisfied(state)) {
+            key.split('/').next().map(|id| id.to_owned())
+        } else {
+            None
+        }
+    }).collect();
+    ids.sort();
+    ids
+}
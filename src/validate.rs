@@ -0,0 +1,143 @@
+use assets::{AssetError, AssetSource};
+use event::{Command, EventLoadError, EventParseError, ParseMode, event_keys, fork_targets, load_event_sources};
+use event::{parse_event, parse_fork_script};
+use map::{MapError, load_map};
+use std::fmt;
+
+pub enum Issue {
+    ParseFailure(EventParseError),
+    UnknownCommand(String, Vec<String>),
+    MissingTexture(String, String),
+    OutOfBounds(String, String, (i32, i32)),
+    MissingMap,
+    /// The matching map exists but couldn't be loaded.
+    BadMap(MapError),
+    /// The events dictionary itself couldn't be read.
+    BadEvents(EventLoadError),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Issue::ParseFailure(ref e) => write!(f, "parse failure: {}", e),
+            Issue::UnknownCommand(ref id, ref args) =>
+                write!(f, "event {}: unknown command {:?}", id, args.join(" ")),
+            Issue::MissingTexture(ref id, ref name) =>
+                write!(f, "event {}: missing texture Characters/{}.xnb", id, name),
+            Issue::OutOfBounds(ref id, ref what, (x, y)) =>
+                write!(f, "event {}: {} at ({}, {}) is outside the map", id, what, x, y),
+            Issue::MissingMap => write!(f, "no matching map in Maps"),
+            Issue::BadMap(ref e) => write!(f, "{}", e),
+            Issue::BadEvents(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The results of checking a single `Data/Events` dictionary.
+pub struct FileReport {
+    pub file: String,
+    pub events: usize,
+    pub issues: Vec<Issue>,
+}
+
+fn in_bounds((x, y): (i32, i32), (w, h): (u32, u32)) -> bool {
+    x >= 0 && y >= 0 && x < w as i32 && y < h as i32
+}
+
+/// Parse every event in the dictionary `file` and check that it refers to
+/// textures that exist and positions within the corresponding map. Fork
+/// scripts are checked as plain command scripts.
+pub fn validate_event_file(assets: &AssetSource, file: &str) -> FileReport {
+    let mut issues = vec![];
+    let sources = match load_event_sources(assets, file) {
+        Ok(sources) => sources,
+        Err(e) => {
+            issues.push(Issue::BadEvents(e));
+            Default::default()
+        }
+    };
+
+    let size = match load_map(assets, file) {
+        Ok(map) => Some(map.layers[0].size),
+        Err(MapError::Missing(_)) => {
+            issues.push(Issue::MissingMap);
+            None
+        }
+        Err(e) => {
+            issues.push(Issue::BadMap(e));
+            None
+        }
+    };
+
+    let mut forks: Vec<_> = fork_targets(&sources).into_iter().filter(|f| sources.contains_key(f)).collect();
+    forks.sort();
+    for name in &forks {
+        let script = match parse_fork_script(name.clone(), &sources[name], ParseMode::Lenient) {
+            Ok(script) => script,
+            Err(e) => {
+                issues.push(Issue::ParseFailure(e));
+                continue;
+            }
+        };
+        for command in &script.commands {
+            if let Command::Unknown(ref args) = *command {
+                issues.push(Issue::UnknownCommand(name.clone(), args.clone()));
+            }
+        }
+    }
+
+    let keys = event_keys(&sources);
+    for key in &keys {
+        let id = key.split('/').next().unwrap_or("").to_owned();
+        let event = match parse_event(key, &sources, ParseMode::Lenient) {
+            Ok(event) => event,
+            Err(e) => {
+                issues.push(Issue::ParseFailure(e));
+                continue;
+            }
+        };
+
+        for command in &event.commands {
+            if let Command::Unknown(ref args) = *command {
+                issues.push(Issue::UnknownCommand(id.clone(), args.clone()));
+            }
+        }
+
+        for character in &event.characters {
+            if character.name != "farmer" &&
+                !assets.exists(format!("Characters/{}.xnb", character.name)) &&
+                !assets.exists(format!("Characters/{}.png", character.name))
+            {
+                issues.push(Issue::MissingTexture(id.clone(), character.name.clone()));
+            }
+        }
+
+        if let Some(size) = size {
+            if !in_bounds(event.viewport, size) {
+                issues.push(Issue::OutOfBounds(id.clone(), "viewport".to_owned(), event.viewport));
+            }
+            for character in &event.characters {
+                if !in_bounds(character.pos, size) {
+                    issues.push(Issue::OutOfBounds(id.clone(), character.name.clone(), character.pos));
+                }
+            }
+        }
+    }
+
+    FileReport {
+        file: file.to_owned(),
+        events: keys.len(),
+        issues: issues,
+    }
+}
+
+/// Validate every `.xnb` dictionary in `Data/Events`.
+pub fn validate_events(assets: &AssetSource) -> Result<Vec<FileReport>, AssetError> {
+    assets.find("Data/Events")?;
+    let files: Vec<_> = assets.list("Data/Events")
+        .into_iter()
+        .filter(|name| name.ends_with(".xnb"))
+        .collect();
+
+    Ok(files.iter().map(|f| validate_event_file(assets, f)).collect())
+}